@@ -0,0 +1,179 @@
+use std::collections::{BTreeMap, BTreeSet};
+use quote::ToTokens;
+use syn::{GenericArgument, PathArguments, ReturnType, Type};
+
+#[derive(Default)]
+pub struct CTypes {
+    pub opaque: BTreeSet<String>,
+    pub structs: BTreeMap<String, String>,
+}
+
+impl CTypes {
+    pub fn declaration(&mut self, ty: &Type, declarator: &str) -> String {
+        self.render(ty, false, false, declarator.to_string())
+    }
+
+    pub fn function(&mut self, name: &str, params: &[(String, Type)], ret: Option<&Type>) -> String {
+        let params = if params.is_empty() {
+            "void".to_string()
+        } else {
+            params.iter().map(|(name, ty)| self.declaration(ty, name)).collect::<Vec<_>>().join(", ")
+        };
+        let declarator = format!("{}({})", name, params);
+        match ret {
+            Some(ret) => self.declaration(ret, &declarator),
+            None => format!("void {}", declarator),
+        }
+    }
+
+    fn render(&mut self, ty: &Type, is_const: bool, behind_pointer: bool, declarator: String) -> String {
+        match ty {
+            Type::Ptr(ptr) => {
+                let declarator = format!("*{}{}", if is_const { "const " } else { "" }, declarator);
+                self.render(&ptr.elem, ptr.const_token.is_some(), true, declarator)
+            }
+            Type::Reference(reference) => {
+                let declarator = format!("*{}{}", if is_const { "const " } else { "" }, declarator);
+                self.render(&reference.elem, reference.mutability.is_none(), true, declarator)
+            }
+            Type::Array(array) => {
+                let declarator = format!("{}[{}]", parenthesise(declarator), array.len.to_token_stream());
+                self.render(&array.elem, is_const, behind_pointer, declarator)
+            }
+            Type::BareFn(function) => {
+                let params = if function.inputs.is_empty() {
+                    "void".to_string()
+                } else {
+                    function.inputs.iter().enumerate().map(|(i, arg)| {
+                        let name = arg.name.as_ref().map_or_else(|| format!("arg{}", i), |(name, _)| name.to_string());
+                        self.declaration(&arg.ty, &name)
+                    }).collect::<Vec<_>>().join(", ")
+                };
+                let declarator = format!("(*{}{})({})", if is_const { "const " } else { "" }, declarator, params);
+                match &function.output {
+                    ReturnType::Default => format!("void {}", declarator),
+                    ReturnType::Type(_, ret) => self.render(ret, false, false, declarator),
+                }
+            }
+            Type::Paren(paren) => self.render(&paren.elem, is_const, behind_pointer, declarator),
+            Type::Group(group) => self.render(&group.elem, is_const, behind_pointer, declarator),
+            Type::Tuple(tuple) if tuple.elems.is_empty() => base("void", is_const, &declarator),
+            Type::Path(path) if path.qself.is_none() => {
+                let segment = path.path.segments.last().expect("type paths have at least one segment");
+                if segment.ident == "Option" {
+                    if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                        if let Some(GenericArgument::Type(inner @ (Type::BareFn(_) | Type::Ptr(_)))) = args.args.first() {
+                            return self.render(inner, is_const, behind_pointer, declarator);
+                        }
+                        if let Some(GenericArgument::Type(inner @ Type::Path(path))) = args.args.first() {
+                            if path.path.segments.last().is_some_and(|segment| segment.ident == "Box") {
+                                return self.render(inner, is_const, behind_pointer, declarator);
+                            }
+                        }
+                    }
+                }
+                if segment.ident == "Box" {
+                    if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                        if let Some(GenericArgument::Type(inner)) = args.args.first() {
+                            let declarator = format!("*{}{}", if is_const { "const " } else { "" }, declarator);
+                            return self.render(inner, false, true, declarator);
+                        }
+                    }
+                }
+                if segment.ident == "Tagged" {
+                    if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                        if let Some(GenericArgument::Type(inner)) = args.args.first() {
+                            return self.render(inner, is_const, behind_pointer, declarator);
+                        }
+                    }
+                }
+                if segment.ident == "FfiBuffer" || segment.ident == "FfiResult" {
+                    if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                        if let Some(GenericArgument::Type(element)) = args.args.first() {
+                            let element_name = self.declaration(element, "");
+                            let name = format!("{}_{}", segment.ident, element_name.replace(" *", "_ptr").replace('*', "ptr").replace(' ', "_"));
+                            if !self.structs.contains_key(&name) {
+                                let fields = if segment.ident == "FfiBuffer" {
+                                    vec![self.declaration(&syn::parse_quote!(*mut #element), "ptr"), "size_t len".to_string(), "size_t cap".to_string()]
+                                } else if element_name == "void" {
+                                    vec!["int32_t status".to_string(), "char *error".to_string()]
+                                } else {
+                                    vec!["int32_t status".to_string(), self.declaration(element, "value"), "char *error".to_string()]
+                                };
+                                let fields = fields.iter().map(|field| format!("    {};\n", field)).collect::<String>();
+                                self.structs.insert(name.clone(), format!("typedef struct {} {{\n{}}} {};\n", name, fields, name));
+                            }
+                            return base(&name, is_const, &declarator);
+                        }
+                    }
+                }
+                if segment.ident == "FfiStatus" {
+                    self.structs.entry("FfiStatus".to_string()).or_insert_with(|| "typedef int32_t FfiStatus;\n".to_string());
+                }
+                let name = segment.ident.to_string();
+                let c_name = match primitive(&name) {
+                    Some(c_name) => c_name.to_string(),
+                    None => {
+                        if behind_pointer {
+                            self.opaque.insert(name.clone());
+                        }
+                        name
+                    }
+                };
+                base(&c_name, is_const, &declarator)
+            }
+            ty => base(&format!("/* unsupported type `{}` */ void", ty.to_token_stream()), is_const, &declarator),
+        }
+    }
+}
+
+fn base(name: &str, is_const: bool, declarator: &str) -> String {
+    let name = if is_const { format!("const {}", name) } else { name.to_string() };
+    if declarator.is_empty() {
+        name
+    } else {
+        format!("{} {}", name, declarator)
+    }
+}
+
+fn parenthesise(declarator: String) -> String {
+    if declarator.starts_with('*') {
+        format!("({})", declarator)
+    } else {
+        declarator
+    }
+}
+
+fn primitive(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "i8" => "int8_t",
+        "i16" => "int16_t",
+        "i32" => "int32_t",
+        "i64" => "int64_t",
+        "u8" => "uint8_t",
+        "u16" => "uint16_t",
+        "u32" => "uint32_t",
+        "u64" => "uint64_t",
+        "usize" => "size_t",
+        "isize" => "ptrdiff_t",
+        "f32" => "float",
+        "f64" => "double",
+        "bool" => "bool",
+        "c_char" => "char",
+        "c_schar" => "signed char",
+        "c_uchar" => "unsigned char",
+        "c_short" => "short",
+        "c_ushort" => "unsigned short",
+        "c_int" => "int",
+        "c_uint" => "unsigned int",
+        "c_long" => "long",
+        "c_ulong" => "unsigned long",
+        "c_longlong" => "long long",
+        "c_ulonglong" => "unsigned long long",
+        "c_float" => "float",
+        "c_double" => "double",
+        "c_void" => "void",
+        "FfiStatus" => "FfiStatus",
+        _ => return None,
+    })
+}
@@ -0,0 +1,154 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{Ident, Token};
+
+pub struct ErrorApiOptions {
+    pub prefix: Ident,
+}
+
+impl Parse for ErrorApiOptions {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let key: Ident = input.parse()?;
+        if key != "prefix" {
+            return Err(syn::Error::new(key.span(), "expected `prefix = ...`"));
+        }
+        input.parse::<Token![=]>()?;
+        let prefix = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(ErrorApiOptions { prefix })
+    }
+}
+
+impl ErrorApiOptions {
+    pub fn last_error_function(&self) -> String {
+        format!("{}_GetLastError", self.prefix)
+    }
+
+    pub fn expand(&self) -> TokenStream {
+        let name = |name: &str| format_ident!("{}_{}", self.prefix, name, span = self.prefix.span());
+        let get_last_error = name("GetLastError");
+        let get_last_error_code = name("GetLastErrorCode");
+        let get_last_error_context = name("GetLastErrorContext");
+        let get_last_error_message = name("GetLastErrorMessage");
+        let get_last_error_sequence = name("GetLastErrorSequence");
+        let has_last_error = name("HasLastError");
+        let clear_last_error = name("ClearLastError");
+        let write_last_error = name("WriteLastError");
+        let get_last_error_source_count = name("GetLastErrorSourceCount");
+        let get_last_error_source = name("GetLastErrorSource");
+        let get_last_error_backtrace = name("GetLastErrorBacktrace");
+        let get_error_stack_depth = name("GetErrorStackDepth");
+        let get_error_stack_frame = name("GetErrorStackFrame");
+        let clear_error_stack = name("ClearErrorStack");
+        let set_callback_error = name("SetCallbackError");
+        let free_string = name("FreeString");
+        let free_wide_string = name("FreeWideString");
+        quote! {
+            /// Returns the last error recorded on this thread, or NULL if there isn't one.
+            #[no_mangle]
+            pub extern "C" fn #get_last_error() -> *mut ::std::os::raw::c_char {
+                ::ffi_utils::last_error_to_ptr()
+            }
+
+            /// Returns the code of the last error recorded on this thread, or 0 if there isn't one.
+            #[no_mangle]
+            pub extern "C" fn #get_last_error_code() -> i32 {
+                ::ffi_utils::get_last_error_code()
+            }
+
+            /// Returns the context of the last error recorded on this thread (what was being done when it happened), or NULL if there isn't one.
+            #[no_mangle]
+            pub extern "C" fn #get_last_error_context() -> *mut ::std::os::raw::c_char {
+                ::ffi_utils::last_error_context_to_ptr()
+            }
+
+            /// Returns the message of the last error recorded on this thread, without its context, or NULL if there isn't one.
+            #[no_mangle]
+            pub extern "C" fn #get_last_error_message() -> *mut ::std::os::raw::c_char {
+                ::ffi_utils::last_error_message_to_ptr()
+            }
+
+            /// Returns a counter that goes up every time an error is recorded on this thread.
+            #[no_mangle]
+            pub extern "C" fn #get_last_error_sequence() -> u64 {
+                ::ffi_utils::get_last_error_sequence()
+            }
+
+            /// Returns 1 if there is a last error recorded on this thread, or 0 if there isn't.
+            #[no_mangle]
+            pub extern "C" fn #has_last_error() -> u8 {
+                ::ffi_utils::bool_to_u8(::ffi_utils::has_last_error())
+            }
+
+            /// Clears the last error recorded on this thread.
+            #[no_mangle]
+            pub extern "C" fn #clear_last_error() {
+                ::ffi_utils::clear_last_error()
+            }
+
+            /// Writes the last error recorded on this thread into `buf`, which is `buf_len` bytes long.
+            /// The size needed (including the NUL terminator) is written to `out_required_len` if it isn't NULL.
+            /// Returns 1 if the whole message fit, or 0 if it was truncated.
+            #[no_mangle]
+            pub extern "C" fn #write_last_error(buf: *mut ::std::os::raw::c_char, buf_len: usize, out_required_len: *mut usize) -> u8 {
+                ::ffi_utils::bool_to_u8(::ffi_utils::write_last_error_to_buffer(buf, buf_len, out_required_len))
+            }
+
+            /// Returns the number of errors in the source chain of the last error.
+            #[no_mangle]
+            pub extern "C" fn #get_last_error_source_count() -> usize {
+                ::ffi_utils::get_last_error_source_count()
+            }
+
+            /// Returns an error from the source chain of the last error, or NULL if `index` is out of range.
+            #[no_mangle]
+            pub extern "C" fn #get_last_error_source(index: usize) -> *mut ::std::os::raw::c_char {
+                ::ffi_utils::last_error_source_to_ptr(index)
+            }
+
+            /// Returns the backtrace of the last error, or NULL if none was captured.
+            #[no_mangle]
+            pub extern "C" fn #get_last_error_backtrace() -> *mut ::std::os::raw::c_char {
+                ::ffi_utils::last_error_backtrace_to_ptr()
+            }
+
+            /// Returns the number of frames in this thread's error stack.
+            #[no_mangle]
+            pub extern "C" fn #get_error_stack_depth() -> usize {
+                ::ffi_utils::get_error_stack_depth()
+            }
+
+            /// Returns a frame of this thread's error stack (0 is the most recent), or NULL if `index` is out of range.
+            #[no_mangle]
+            pub extern "C" fn #get_error_stack_frame(index: usize) -> *mut ::std::os::raw::c_char {
+                ::ffi_utils::error_stack_frame_to_ptr(index)
+            }
+
+            /// Clears this thread's error stack.
+            #[no_mangle]
+            pub extern "C" fn #clear_error_stack() {
+                ::ffi_utils::clear_error_stack()
+            }
+
+            /// Reports why a callback failed, for a callback to call before returning its error value.
+            /// A `code` of 0 is replaced with the generic callback error code. `message` may be NULL.
+            #[no_mangle]
+            pub extern "C" fn #set_callback_error(code: i32, message: *const ::std::os::raw::c_char) {
+                ::ffi_utils::set_callback_error_from_ptr(code, message)
+            }
+
+            /// Frees a string returned by this library. Does nothing if `string` is NULL.
+            #[no_mangle]
+            pub extern "C" fn #free_string(string: *mut ::std::os::raw::c_char) {
+                let _ = ::ffi_utils::take_string_ownership(string);
+            }
+
+            /// Frees a wide string returned by this library. Does nothing if `string` is NULL.
+            #[no_mangle]
+            pub extern "C" fn #free_wide_string(string: *mut u16) {
+                let _ = ::ffi_utils::take_wstring_ownership(string);
+            }
+        }
+    }
+}
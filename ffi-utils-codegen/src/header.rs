@@ -0,0 +1,489 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use syn::{Attribute, Expr, ExprLit, FnArg, GenericArgument, Item, ItemFn, Lit, Meta, Pat, PathArguments, ReturnType, Type};
+use crate::ctype::CTypes;
+use crate::error_api::ErrorApiOptions;
+use crate::signature::{ExportOptions, ExportSignature, ParamKind, ValueKind};
+
+#[derive(Debug)]
+pub enum HeaderError {
+    Io { path: PathBuf, error: io::Error },
+    Parse { path: PathBuf, error: syn::Error },
+}
+
+impl Display for HeaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeaderError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            HeaderError::Parse { path, error } => {
+                let start = error.span().start();
+                write!(f, "{}:{}:{}: {}", path.display(), start.line, start.column + 1, error)
+            }
+        }
+    }
+}
+
+impl Error for HeaderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HeaderError::Io { error, .. } => Some(error),
+            HeaderError::Parse { error, .. } => Some(error),
+        }
+    }
+}
+
+enum Sentinel {
+    None,
+    Null,
+    Value(String),
+    Status,
+    Code,
+}
+
+struct ExportedFunction {
+    name: String,
+    docs: Vec<String>,
+    params: Vec<(String, Type)>,
+    param_notes: Vec<String>,
+    ret: Option<Type>,
+    sentinel: Sentinel,
+    is_last_error: bool,
+}
+
+#[derive(Default)]
+pub struct HeaderBuilder {
+    sources: Vec<PathBuf>,
+    include_guard: Option<String>,
+    string_free_function: Option<String>,
+    last_error_function: Option<String>,
+    object_free_functions: BTreeMap<String, String>,
+}
+
+impl HeaderBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn source<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.sources.push(path.into());
+        self
+    }
+
+    pub fn include_guard<S: Into<String>>(mut self, include_guard: S) -> Self {
+        self.include_guard = Some(include_guard.into());
+        self
+    }
+
+    pub fn string_free_function<S: Into<String>>(mut self, name: S) -> Self {
+        self.string_free_function = Some(name.into());
+        self
+    }
+
+    pub fn last_error_function<S: Into<String>>(mut self, name: S) -> Self {
+        self.last_error_function = Some(name.into());
+        self
+    }
+
+    pub fn object_free_function<T: Into<String>, S: Into<String>>(mut self, type_name: T, name: S) -> Self {
+        self.object_free_functions.insert(type_name.into(), name.into());
+        self
+    }
+
+    pub fn generate(&self) -> Result<String, HeaderError> {
+        self.generate_with_guard(self.include_guard.as_deref().unwrap_or("FFI_UTILS_GENERATED_H"))
+    }
+
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), HeaderError> {
+        let path = path.as_ref();
+        let guard = match &self.include_guard {
+            Some(guard) => guard.clone(),
+            None => path.file_name().map_or_else(String::new, |name| name.to_string_lossy().to_string())
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+                .collect(),
+        };
+        let header = self.generate_with_guard(&guard)?;
+        let io_error = |error| HeaderError::Io { path: path.to_path_buf(), error };
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        fs::write(path, header).map_err(io_error)
+    }
+
+    fn generate_with_guard(&self, guard: &str) -> Result<String, HeaderError> {
+        let mut functions = Vec::new();
+        for source in &self.sources {
+            let dir = module_dir(source);
+            collect_file(source, &dir, &mut functions)?;
+        }
+
+        let last_error_function = self.last_error_function.clone()
+            .or_else(|| functions.iter().find(|function| function.is_last_error).map(|function| function.name.clone()));
+        let mut string_free_function = self.string_free_function.clone();
+        let mut object_free_functions = self.object_free_functions.clone();
+        for function in &functions {
+            if let Some(type_name) = freed_type(function) {
+                if type_name == "c_char" {
+                    string_free_function.get_or_insert_with(|| function.name.clone());
+                } else {
+                    object_free_functions.entry(type_name).or_insert_with(|| function.name.clone());
+                }
+            }
+        }
+
+        let mut types = CTypes::default();
+        let mut declarations = String::new();
+        for function in &functions {
+            let mut docs = function.docs.clone();
+            let mut notes = function.param_notes.clone();
+            let is_last_error_function = last_error_function.as_deref() == Some(function.name.as_str());
+            match &function.sentinel {
+                _ if is_last_error_function => {}
+                Sentinel::None => {}
+                Sentinel::Null => notes.push("Returns NULL on error.".to_string()),
+                Sentinel::Value(value) => notes.push(format!("Returns {} on error.", value)),
+                Sentinel::Status => notes.push("Returns 1 on success or 0 on error.".to_string()),
+                Sentinel::Code => notes.push("Returns 0 on success or an error code on error.".to_string()),
+            }
+            if !is_last_error_function && !matches!(function.sentinel, Sentinel::None) {
+                notes.push(match &last_error_function {
+                    Some(last_error) => format!("Call {}() for details of the error.", last_error),
+                    None => "Check the last error for details of the error.".to_string(),
+                });
+            }
+            if function.ret.as_ref().and_then(type_name).as_deref() == Some("FfiResult") {
+                notes.push(match &string_free_function {
+                    Some(free) => format!("The returned result's `error` must be freed with {}() if it isn't NULL.", free),
+                    None => "The returned result's `error` must be freed with the library's string free function if it isn't NULL.".to_string(),
+                });
+            }
+            if let Some(Type::Ptr(ptr)) = &function.ret {
+                let pointee = type_name(&ptr.elem);
+                match (ptr.mutability.is_some(), pointee.as_deref()) {
+                    (true, Some("c_char")) => notes.push(match &string_free_function {
+                        Some(free) => format!("The returned string must be freed with {}().", free),
+                        None => "The returned string must be freed with the library's string free function.".to_string(),
+                    }),
+                    (false, Some("c_char")) => notes.push("The returned string is borrowed and must not be freed.".to_string()),
+                    (true, Some(pointee)) => {
+                        if let Some(free) = object_free_functions.get(pointee) {
+                            notes.push(format!("The returned object must be freed with {}().", free));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            match function.ret.as_ref().and_then(type_name).as_deref() {
+                Some("FfiBuffer") => {
+                    let name = types.declaration(function.ret.as_ref().expect("named types exist"), "");
+                    notes.push(match object_free_functions.get(&name) {
+                        Some(free) => format!("The returned buffer must be freed with {}().", free),
+                        None => "The returned buffer must be freed with the library's buffer free function.".to_string(),
+                    });
+                }
+                Some("Box") => {
+                    if let Some(free) = function.ret.as_ref().and_then(inner_type).and_then(type_name).and_then(|pointee| object_free_functions.get(&pointee)) {
+                        notes.push(format!("The returned object must be freed with {}().", free));
+                    }
+                }
+                _ => {}
+            }
+            if !notes.is_empty() {
+                if !docs.is_empty() {
+                    docs.push(String::new());
+                }
+                docs.append(&mut notes);
+            }
+
+            declarations.push('\n');
+            if !docs.is_empty() {
+                declarations.push_str("/**\n");
+                for line in docs {
+                    declarations.push_str(format!(" * {}", line).trim_end());
+                    declarations.push('\n');
+                }
+                declarations.push_str(" */\n");
+            }
+            declarations.push_str(&types.function(&function.name, &function.params, function.ret.as_ref()));
+            declarations.push_str(";\n");
+        }
+
+        let mut header = String::new();
+        header.push_str("/* Generated by ffi-utils-codegen. Do not edit by hand. */\n\n");
+        header.push_str(&format!("#ifndef {}\n#define {}\n\n", guard, guard));
+        header.push_str("#include <stdbool.h>\n#include <stddef.h>\n#include <stdint.h>\n\n");
+        header.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n");
+        if !types.opaque.is_empty() {
+            header.push('\n');
+            for name in &types.opaque {
+                header.push_str(&format!("typedef struct {} {};\n", name, name));
+            }
+        }
+        for definition in types.structs.values() {
+            header.push('\n');
+            header.push_str(definition);
+        }
+        header.push_str(&declarations);
+        header.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
+        header.push_str(&format!("#endif /* {} */\n", guard));
+        Ok(header)
+    }
+}
+
+fn module_dir(path: &Path) -> PathBuf {
+    let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some("lib.rs" | "main.rs" | "mod.rs") => parent,
+        _ => parent.join(path.file_stem().unwrap_or_default()),
+    }
+}
+
+fn collect_file(path: &Path, dir: &Path, functions: &mut Vec<ExportedFunction>) -> Result<(), HeaderError> {
+    let source = fs::read_to_string(path).map_err(|error| HeaderError::Io { path: path.to_path_buf(), error })?;
+    let file = syn::parse_file(&source).map_err(|error| HeaderError::Parse { path: path.to_path_buf(), error })?;
+    collect_items(path, dir, &file.items, functions)
+}
+
+fn collect_items(path: &Path, dir: &Path, items: &[Item], functions: &mut Vec<ExportedFunction>) -> Result<(), HeaderError> {
+    let parse_error = |error| HeaderError::Parse { path: path.to_path_buf(), error };
+    for item in items {
+        match item {
+            Item::Fn(function) => {
+                if let Some(attr) = function.attrs.iter().find(|attr| is_ffi_export(attr)) {
+                    functions.push(ffi_export_function(function, attr).map_err(parse_error)?);
+                } else if function.sig.abi.is_some() && function.attrs.iter().any(is_no_mangle) {
+                    functions.push(raw_function(function));
+                }
+            }
+            Item::Mod(module) => {
+                let dir = dir.join(module.ident.to_string());
+                match &module.content {
+                    Some((_, items)) => collect_items(path, &dir, items, functions)?,
+                    None => {
+                        let file = dir.with_extension("rs");
+                        if file.exists() {
+                            collect_file(&file, &dir, functions)?;
+                        } else {
+                            collect_file(&dir.join("mod.rs"), &dir, functions)?;
+                        }
+                    }
+                }
+            }
+            Item::Macro(item) if item.mac.path.segments.last().is_some_and(|segment| segment.ident == "export_error_api") => {
+                let options: ErrorApiOptions = item.mac.parse_body().map_err(parse_error)?;
+                let file: syn::File = syn::parse2(options.expand()).map_err(parse_error)?;
+                for item in &file.items {
+                    if let Item::Fn(function) = item {
+                        let mut function = raw_function(function);
+                        function.sentinel = Sentinel::None;
+                        function.is_last_error = function.name == options.last_error_function();
+                        functions.push(function);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn is_ffi_export(attr: &Attribute) -> bool {
+    attr.path().segments.last().is_some_and(|segment| segment.ident == "ffi_export")
+}
+
+fn is_no_mangle(attr: &Attribute) -> bool {
+    match &attr.meta {
+        Meta::Path(path) => path.is_ident("no_mangle"),
+        Meta::List(list) => list.path.is_ident("unsafe") && list.tokens.to_string() == "no_mangle",
+        Meta::NameValue(_) => false,
+    }
+}
+
+fn docs(attrs: &[Attribute]) -> Vec<String> {
+    attrs.iter().filter_map(|attr| match &attr.meta {
+        Meta::NameValue(meta) if meta.path.is_ident("doc") => match &meta.value {
+            Expr::Lit(ExprLit { lit: Lit::Str(doc), .. }) => Some(doc.value().strip_prefix(' ').map(str::to_string).unwrap_or_else(|| doc.value())),
+            _ => None,
+        },
+        _ => None,
+    }).collect()
+}
+
+fn ffi_export_function(function: &ItemFn, attr: &Attribute) -> syn::Result<ExportedFunction> {
+    let mut options = ExportOptions::default();
+    if let Meta::List(_) = attr.meta {
+        attr.parse_nested_meta(|meta| options.parse(meta))?;
+    }
+    let signature = ExportSignature::parse(&function.sig)?;
+    let error = signature.error_value(&options, &function.sig)?;
+    let sentinel = match (&signature.ret.value, signature.ret.fallible) {
+        (ValueKind::Unit, false) => Sentinel::None,
+        (ValueKind::Unit, true) => Sentinel::Status,
+        (ValueKind::String | ValueKind::Boxed(_), _) => Sentinel::Null,
+        (ValueKind::Bool, _) if error.value.to_string() == "false" => Sentinel::Value("0".to_string()),
+        (ValueKind::Bool, _) if error.value.to_string() == "true" => Sentinel::Value("1".to_string()),
+        _ => Sentinel::Value(expression(&error.value.to_string())),
+    };
+    Ok(ExportedFunction {
+        name: options.name.as_ref().unwrap_or(&function.sig.ident).to_string(),
+        docs: docs(&function.attrs),
+        params: signature.params.iter().map(|param| (param.ident.to_string(), param.ffi_type())).collect(),
+        param_notes: signature.params.iter().filter_map(|param| match param.kind {
+            ParamKind::Str { not_null: false } => Some(format!("`{}` may be NULL, which is treated as an empty string.", param.ident)),
+            ParamKind::Str { not_null: true } => Some(format!("`{}` must not be NULL.", param.ident)),
+            ParamKind::OptStr => Some(format!("`{}` may be NULL.", param.ident)),
+            _ => None,
+        }).collect(),
+        ret: signature.ret.ffi_type(),
+        sentinel,
+        is_last_error: false,
+    })
+}
+
+fn raw_function(function: &ItemFn) -> ExportedFunction {
+    let params = function.sig.inputs.iter().enumerate().filter_map(|(i, arg)| match arg {
+        FnArg::Typed(arg) => {
+            let name = match arg.pat.as_ref() {
+                Pat::Ident(pat) => pat.ident.to_string(),
+                _ => format!("arg{}", i),
+            };
+            Some((name, (*arg.ty).clone()))
+        }
+        FnArg::Receiver(_) => None,
+    }).collect();
+    let ret = match &function.sig.output {
+        ReturnType::Default => None,
+        ReturnType::Type(_, ty) => Some((**ty).clone()).filter(|ty| !matches!(ty, Type::Tuple(tuple) if tuple.elems.is_empty())),
+    };
+    let sentinel = match &ret {
+        Some(Type::Ptr(_)) => Sentinel::Null,
+        Some(ty) if type_name(ty).as_deref() == Some("FfiStatus") => Sentinel::Code,
+        _ => Sentinel::None,
+    };
+    ExportedFunction { name: function.sig.ident.to_string(), docs: docs(&function.attrs), params, param_notes: Vec::new(), ret, sentinel, is_last_error: false }
+}
+
+fn freed_type(function: &ExportedFunction) -> Option<String> {
+    if function.ret.is_some() || function.params.len() != 1 || !function.name.to_ascii_lowercase().contains("free") {
+        return None;
+    }
+    match &function.params[0].1 {
+        Type::Ptr(ptr) if ptr.mutability.is_some() => type_name(&ptr.elem),
+        ty => match type_name(ty).as_deref() {
+            Some("Box") => inner_type(ty).and_then(type_name),
+            Some("FfiBuffer") => Some(CTypes::default().declaration(ty, "")),
+            _ => None,
+        },
+    }
+}
+
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => {
+            let segment = path.path.segments.last()?;
+            match inner_type(ty) {
+                Some(inner) if segment.ident == "Tagged" => type_name(inner),
+                _ => Some(segment.ident.to_string()),
+            }
+        }
+        _ => None,
+    }
+}
+
+fn inner_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    match &path.path.segments.last()?.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first() {
+            Some(GenericArgument::Type(inner)) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn expression(tokens: &str) -> String {
+    match tokens.strip_prefix("- ") {
+        Some(rest) => format!("-{}", rest),
+        None => tokens.to_string(),
+    }.replace(" :: ", "::")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate(name: &str, source: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("ffi-utils-codegen-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("lib.rs");
+        fs::write(&path, source).unwrap();
+        let header = HeaderBuilder::new().source(&path).include_guard("TEST_H").generate();
+        fs::remove_dir_all(&dir).unwrap();
+        header.unwrap()
+    }
+
+    #[test]
+    fn ffi_export_functions() {
+        let header = generate("ffi-export", r#"
+            /// Creates a thing.
+            #[ffi_export]
+            fn thing_new(#[not_null] name: &str) -> Box<Thing> {}
+
+            #[ffi_export]
+            fn thing_free(thing: Box<Thing>) {}
+
+            #[ffi_export(error = -1, name = "Thing_Rename")]
+            fn rename(thing: &mut Thing, name: Option<&str>, force: bool) -> Result<i32, String> {}
+
+            #[ffi_export]
+            fn thing_validate(thing: &Thing) -> Result<(), String> {}
+
+            #[ffi_export]
+            fn thing_describe(thing: &Thing, prefix: &str) -> String {}
+
+            #[ffi_export]
+            fn string_free(string: *mut c_char) {}
+        "#);
+        assert_eq!(header, include_str!("../tests/headers/ffi_export.h"));
+    }
+
+    #[test]
+    fn extern_functions() {
+        let header = generate("extern", r#"
+            #[no_mangle]
+            pub extern "C" fn Thing_GetData(thing: *const Thing) -> FfiBuffer<u8> {}
+
+            #[no_mangle]
+            pub extern "C" fn Thing_GetNames(thing: *const Thing) -> FfiBuffer<*mut c_char> {}
+
+            #[no_mangle]
+            pub extern "C" fn FreeBytes(buffer: FfiBuffer<u8>) {}
+
+            #[no_mangle]
+            pub extern "C" fn Thing_Clone(thing: &Thing) -> Box<Thing> {}
+
+            #[no_mangle]
+            pub extern "C" fn Thing_Free(thing: *mut Thing) {}
+
+            #[no_mangle]
+            pub extern "C" fn Thing_Take(thing: Option<Box<Thing>>, out_len: *mut usize) -> FfiStatus {}
+
+            #[no_mangle]
+            pub extern "C" fn Thing_Parse(text: *const c_char) -> FfiResult<i32> {}
+
+            #[no_mangle]
+            pub extern "C" fn Thing_Name(thing: *const Thing) -> *const c_char {}
+
+            #[no_mangle]
+            pub extern "C" fn Thing_SetCallback(thing: *mut Thing, callback: Option<extern "C" fn(user_data: *mut c_void, values: *const [i32; 4]) -> bool>) {}
+        "#);
+        assert_eq!(header, include_str!("../tests/headers/extern.h"));
+    }
+}
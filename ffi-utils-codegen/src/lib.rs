@@ -0,0 +1,6 @@
+mod ctype;
+pub mod error_api;
+mod header;
+pub mod signature;
+
+pub use header::*;
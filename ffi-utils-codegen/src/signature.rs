@@ -0,0 +1,208 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::{Attribute, Expr, FnArg, GenericArgument, Ident, LitStr, Pat, PathArguments, ReturnType, Signature, Type};
+
+#[derive(Default)]
+pub struct ExportOptions {
+    pub context: Option<LitStr>,
+    pub error: Option<Expr>,
+    pub name: Option<Ident>,
+}
+
+impl ExportOptions {
+    pub fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("context") {
+            self.context = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("error") {
+            self.error = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("name") {
+            let name: LitStr = meta.value()?.parse()?;
+            self.name = Some(name.parse()?);
+        } else {
+            return Err(meta.error("expected `context`, `error` or `name`"));
+        }
+        Ok(())
+    }
+}
+
+pub enum ParamKind {
+    MutRef(Type),
+    Ref(Type),
+    Str { not_null: bool },
+    OptStr,
+    Bool,
+    Plain(Type),
+}
+
+pub struct Param {
+    pub ident: Ident,
+    pub kind: ParamKind,
+}
+
+impl Param {
+    pub fn ffi_type(&self) -> Type {
+        match &self.kind {
+            ParamKind::MutRef(ty) => syn::parse_quote!(*mut #ty),
+            ParamKind::Ref(ty) => syn::parse_quote!(*const #ty),
+            ParamKind::Str { .. } | ParamKind::OptStr => syn::parse_quote!(*const ::std::os::raw::c_char),
+            ParamKind::Bool => syn::parse_quote!(u8),
+            ParamKind::Plain(ty) => ty.clone(),
+        }
+    }
+}
+
+pub enum ValueKind {
+    Unit,
+    Bool,
+    String,
+    Boxed(Type),
+    Plain(Type),
+}
+
+pub struct Return {
+    pub fallible: bool,
+    pub value: ValueKind,
+}
+
+impl Return {
+    pub fn ffi_type(&self) -> Option<Type> {
+        match (&self.value, self.fallible) {
+            (ValueKind::Unit, false) => None,
+            (ValueKind::Unit, true) | (ValueKind::Bool, _) => Some(syn::parse_quote!(u8)),
+            (ValueKind::String, _) => Some(syn::parse_quote!(*mut ::std::os::raw::c_char)),
+            (ValueKind::Boxed(ty), _) => Some(syn::parse_quote!(*mut #ty)),
+            (ValueKind::Plain(ty), _) => Some(ty.clone()),
+        }
+    }
+}
+
+pub struct ExportSignature {
+    pub params: Vec<Param>,
+    pub ret: Return,
+}
+
+pub struct ErrorValue {
+    pub value: TokenStream,
+    pub is_bool: bool,
+}
+
+impl ExportSignature {
+    pub fn parse(sig: &Signature) -> syn::Result<Self> {
+        if !sig.generics.params.is_empty() {
+            return Err(syn::Error::new_spanned(&sig.generics, "ffi_export functions cannot be generic"));
+        }
+        if let Some(token) = &sig.asyncness {
+            return Err(syn::Error::new_spanned(token, "ffi_export functions cannot be async"));
+        }
+        let params = sig.inputs.iter().map(parse_param).collect::<syn::Result<_>>()?;
+        let ret = match &sig.output {
+            ReturnType::Default => Return { fallible: false, value: ValueKind::Unit },
+            ReturnType::Type(_, ty) => parse_return(ty),
+        };
+        Ok(ExportSignature { params, ret })
+    }
+
+    pub fn error_value(&self, options: &ExportOptions, sig: &Signature) -> syn::Result<ErrorValue> {
+        let value = |value, is_bool| Ok(ErrorValue { value, is_bool });
+        match (&self.ret.value, self.ret.fallible, &options.error) {
+            (ValueKind::Unit, false, _) => value(quote!(()), false),
+            (ValueKind::Unit, true, _) => value(quote!(false), true),
+            (ValueKind::Bool, _, Some(error)) => value(quote!(#error), true),
+            (ValueKind::Bool, _, None) => value(quote!(false), true),
+            (ValueKind::String | ValueKind::Boxed(_), _, Some(error)) => {
+                Err(syn::Error::new_spanned(error, "`error` cannot be set for functions returning a pointer, null is always used"))
+            }
+            (ValueKind::String | ValueKind::Boxed(_), _, None) => value(quote!(::std::ptr::null_mut()), false),
+            (ValueKind::Plain(_), _, Some(error)) => value(quote!(#error), false),
+            (ValueKind::Plain(ty), _, None) => Err(syn::Error::new_spanned(
+                ty,
+                format!("`{}` needs an `error = ...` value to return when it fails", sig.ident),
+            )),
+        }
+    }
+}
+
+fn parse_param(arg: &FnArg) -> syn::Result<Param> {
+    let arg = match arg {
+        FnArg::Typed(arg) => arg,
+        FnArg::Receiver(receiver) => return Err(syn::Error::new_spanned(receiver, "ffi_export functions cannot take self")),
+    };
+    let ident = match arg.pat.as_ref() {
+        Pat::Ident(pat) => pat.ident.clone(),
+        pat => return Err(syn::Error::new_spanned(pat, "ffi_export parameters must be plain identifiers")),
+    };
+    let not_null = arg.attrs.iter().find(|attr| is_not_null(attr));
+    let kind = match arg.ty.as_ref() {
+        Type::Reference(reference) if is_path(&reference.elem, "str") => {
+            if reference.mutability.is_some() {
+                return Err(syn::Error::new_spanned(reference, "ffi_export cannot pass `&mut str` parameters"));
+            }
+            ParamKind::Str { not_null: not_null.is_some() }
+        }
+        ty if generic_args(ty, "Option").is_some_and(|args| args.len() == 1 && matches!(&args[0], Type::Reference(r) if r.mutability.is_none() && is_path(&r.elem, "str"))) => {
+            ParamKind::OptStr
+        }
+        Type::Reference(reference) if reference.mutability.is_some() => ParamKind::MutRef((*reference.elem).clone()),
+        Type::Reference(reference) => ParamKind::Ref((*reference.elem).clone()),
+        ty if is_path(ty, "bool") => ParamKind::Bool,
+        ty => ParamKind::Plain(ty.clone()),
+    };
+    if let (Some(attr), false) = (not_null, matches!(kind, ParamKind::Str { .. })) {
+        return Err(syn::Error::new_spanned(attr, "`#[not_null]` can only be used on `&str` parameters"));
+    }
+    Ok(Param { ident, kind })
+}
+
+fn is_not_null(attr: &Attribute) -> bool {
+    attr.path().is_ident("not_null")
+}
+
+pub fn strip_param_attributes(sig: &mut Signature) {
+    for arg in sig.inputs.iter_mut() {
+        if let FnArg::Typed(arg) = arg {
+            arg.attrs.retain(|attr| !is_not_null(attr));
+        }
+    }
+}
+
+fn parse_return(ty: &Type) -> Return {
+    match generic_args(ty, "Result").and_then(|args| args.into_iter().next()) {
+        Some(ok) => Return { fallible: true, value: parse_value(&ok) },
+        None => Return { fallible: false, value: parse_value(ty) },
+    }
+}
+
+fn parse_value(ty: &Type) -> ValueKind {
+    if let Type::Tuple(tuple) = ty {
+        if tuple.elems.is_empty() {
+            return ValueKind::Unit;
+        }
+    }
+    if is_path(ty, "bool") {
+        ValueKind::Bool
+    } else if is_path(ty, "String") {
+        ValueKind::String
+    } else if let Some(inner) = generic_args(ty, "Box").and_then(|args| args.into_iter().next()) {
+        ValueKind::Boxed(inner)
+    } else {
+        ValueKind::Plain(ty.clone())
+    }
+}
+
+fn is_path(ty: &Type, name: &str) -> bool {
+    match ty {
+        Type::Path(path) => path.qself.is_none() && path.path.segments.last().is_some_and(|s| s.ident == name && s.arguments.is_none()),
+        _ => false,
+    }
+}
+
+fn generic_args(ty: &Type, name: &str) -> Option<Vec<Type>> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last().filter(|s| s.ident == name)?;
+    let PathArguments::AngleBracketed(args) = &segment.arguments else { return None };
+    Some(args.args.iter().filter_map(|arg| match arg {
+        GenericArgument::Type(ty) => Some(ty.clone()),
+        _ => None,
+    }).collect())
+}
@@ -0,0 +1,79 @@
+/* Generated by ffi-utils-codegen. Do not edit by hand. */
+
+#ifndef TEST_H
+#define TEST_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Thing Thing;
+
+typedef struct FfiBuffer_char_ptr {
+    char **ptr;
+    size_t len;
+    size_t cap;
+} FfiBuffer_char_ptr;
+
+typedef struct FfiBuffer_uint8_t {
+    uint8_t *ptr;
+    size_t len;
+    size_t cap;
+} FfiBuffer_uint8_t;
+
+typedef struct FfiResult_int32_t {
+    int32_t status;
+    int32_t value;
+    char *error;
+} FfiResult_int32_t;
+
+typedef int32_t FfiStatus;
+
+/**
+ * The returned buffer must be freed with FreeBytes().
+ */
+FfiBuffer_uint8_t Thing_GetData(const Thing *thing);
+
+/**
+ * The returned buffer must be freed with the library's buffer free function.
+ */
+FfiBuffer_char_ptr Thing_GetNames(const Thing *thing);
+
+void FreeBytes(FfiBuffer_uint8_t buffer);
+
+/**
+ * The returned object must be freed with Thing_Free().
+ */
+Thing *Thing_Clone(const Thing *thing);
+
+void Thing_Free(Thing *thing);
+
+/**
+ * Returns 0 on success or an error code on error.
+ * Check the last error for details of the error.
+ */
+FfiStatus Thing_Take(Thing *thing, size_t *out_len);
+
+/**
+ * The returned result's `error` must be freed with the library's string free function if it isn't NULL.
+ */
+FfiResult_int32_t Thing_Parse(const char *text);
+
+/**
+ * Returns NULL on error.
+ * Check the last error for details of the error.
+ * The returned string is borrowed and must not be freed.
+ */
+const char *Thing_Name(const Thing *thing);
+
+void Thing_SetCallback(Thing *thing, bool (*callback)(void *user_data, const int32_t (*values)[4]));
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TEST_H */
@@ -0,0 +1,55 @@
+/* Generated by ffi-utils-codegen. Do not edit by hand. */
+
+#ifndef TEST_H
+#define TEST_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Thing Thing;
+
+/**
+ * Creates a thing.
+ *
+ * `name` must not be NULL.
+ * Returns NULL on error.
+ * Check the last error for details of the error.
+ * The returned object must be freed with thing_free().
+ */
+Thing *thing_new(const char *name);
+
+void thing_free(Thing *thing);
+
+/**
+ * `name` may be NULL.
+ * Returns -1 on error.
+ * Check the last error for details of the error.
+ */
+int32_t Thing_Rename(Thing *thing, const char *name, uint8_t force);
+
+/**
+ * Returns 1 on success or 0 on error.
+ * Check the last error for details of the error.
+ */
+uint8_t thing_validate(const Thing *thing);
+
+/**
+ * `prefix` may be NULL, which is treated as an empty string.
+ * Returns NULL on error.
+ * Check the last error for details of the error.
+ * The returned string must be freed with string_free().
+ */
+char *thing_describe(const Thing *thing, const char *prefix);
+
+void string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TEST_H */
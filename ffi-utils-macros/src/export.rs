@@ -0,0 +1,106 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{ItemFn, LitStr};
+use ffi_utils_codegen::signature::{strip_param_attributes, ErrorValue, ExportOptions, ExportSignature, ParamKind, ValueKind};
+
+pub fn expand(options: ExportOptions, function: ItemFn) -> syn::Result<TokenStream> {
+    let signature = ExportSignature::parse(&function.sig)?;
+    let ItemFn { attrs, vis, sig, block } = function;
+    let inner_ident = format_ident!("__ffi_export_{}", sig.ident);
+    let export_ident = options.name.clone().unwrap_or_else(|| sig.ident.clone());
+    let context = options.context.clone().unwrap_or_else(|| LitStr::new(&sig.ident.to_string(), sig.ident.span()));
+    let mut inner_sig = sig.clone();
+    inner_sig.ident = inner_ident.clone();
+    strip_param_attributes(&mut inner_sig);
+
+    let ErrorValue { value: error, is_bool: returns_bool } = signature.error_value(&options, &sig)?;
+    let c_return = signature.ret.ffi_type().map(|ty| quote!(-> #ty));
+    let c_params = signature.params.iter().map(|param| {
+        let (ident, ty) = (&param.ident, param.ffi_type());
+        quote!(#ident: #ty)
+    });
+    let args = signature.params.iter().map(|param| &param.ident);
+    let call = quote!(#inner_ident(#(#args),*));
+
+    let mut body = match (&signature.ret.value, signature.ret.fallible) {
+        (ValueKind::Unit, true) => quote!(::ffi_utils::handle_result(CONTEXT, #error, #call.map(|()| true))),
+        (ValueKind::String, false) => quote!(::ffi_utils::string_to_ptr(CONTEXT, #call)),
+        (ValueKind::String, true) => quote!(::ffi_utils::string_result_to_ptr(CONTEXT, #call)),
+        (ValueKind::Boxed(_), false) => quote!(::std::boxed::Box::into_raw(#call)),
+        (ValueKind::Boxed(_), true) => quote!(::ffi_utils::handle_result(CONTEXT, #error, #call.map(::std::boxed::Box::into_raw))),
+        (_, true) => quote!(::ffi_utils::handle_result(CONTEXT, #error, #call)),
+        (_, false) => call,
+    };
+    for param in signature.params.iter().rev() {
+        let ident = &param.ident;
+        body = match &param.kind {
+            ParamKind::MutRef(_) => quote!(::ffi_utils::with(CONTEXT, #ident, #error, |#ident| #body)),
+            ParamKind::Ref(_) => quote!(::ffi_utils::with_ref(CONTEXT, #ident, #error, |#ident| #body)),
+            ParamKind::Str { not_null: false } => quote!(::ffi_utils::with_str(CONTEXT, #ident, #error, |#ident| #body)),
+            ParamKind::Str { not_null: true } => quote!(::ffi_utils::with_str_strict(CONTEXT, #ident, #error, |#ident| #body)),
+            ParamKind::OptStr => quote!(::ffi_utils::with_opt_str(CONTEXT, #ident, #error, |#ident| #body)),
+            ParamKind::Bool => quote!({ let #ident = ::ffi_utils::u8_to_bool(#ident); #body }),
+            ParamKind::Plain(_) => body,
+        };
+    }
+    body = quote!(::ffi_utils::catch_panic(CONTEXT, #error, || #body));
+    if returns_bool {
+        body = quote!(::ffi_utils::bool_to_u8(#body));
+    }
+
+    Ok(quote! {
+        #(#attrs)*
+        #[no_mangle]
+        #vis extern "C" fn #export_ident(#(#c_params),*) #c_return {
+            #inner_sig #block
+            const CONTEXT: &str = #context;
+            #body
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn options(error: Option<syn::Expr>) -> ExportOptions {
+        ExportOptions { error, ..ExportOptions::default() }
+    }
+
+    fn expand_error(options: ExportOptions, function: ItemFn) -> String {
+        match expand(options, function) {
+            Ok(tokens) => panic!("expected an error, got `{}`", tokens),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn not_null_on_a_non_str_parameter() {
+        let function = parse_quote!(fn f(#[not_null] value: &Thing) -> i32 { 0 });
+        assert_eq!(expand_error(options(Some(parse_quote!(-1))), function), "`#[not_null]` can only be used on `&str` parameters");
+    }
+
+    #[test]
+    fn missing_error_value() {
+        let function = parse_quote!(fn f(value: &Thing) -> Result<i32, String> { Ok(0) });
+        assert_eq!(expand_error(options(None), function), "`f` needs an `error = ...` value to return when it fails");
+    }
+
+    #[test]
+    fn error_value_on_a_pointer_return() {
+        let function = parse_quote!(fn f() -> String { String::new() });
+        assert_eq!(expand_error(options(Some(parse_quote!(-1))), function), "`error` cannot be set for functions returning a pointer, null is always used");
+    }
+
+    #[test]
+    fn unit_result_returns_u8() {
+        let function = parse_quote!(fn f(#[not_null] name: &str) -> Result<(), String> { Ok(()) });
+        let expanded = expand(options(None), function).unwrap().to_string();
+        assert!(expanded.contains(&quote!(extern "C" fn f(name: *const ::std::os::raw::c_char) -> u8).to_string()));
+        assert!(expanded.contains(&quote!(::ffi_utils::with_str_strict).to_string()));
+        assert!(expanded.contains(&quote!(.map(|()| true)).to_string()));
+        assert!(expanded.starts_with(&quote!(#[no_mangle]).to_string()));
+        assert!(!expanded.contains("not_null"));
+    }
+}
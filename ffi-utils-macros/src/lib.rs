@@ -0,0 +1,20 @@
+mod export;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, ItemFn};
+use ffi_utils_codegen::error_api::ErrorApiOptions;
+use ffi_utils_codegen::signature::ExportOptions;
+
+#[proc_macro_attribute]
+pub fn ffi_export(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut options = ExportOptions::default();
+    let parser = syn::meta::parser(|meta| options.parse(meta));
+    parse_macro_input!(args with parser);
+    let function = parse_macro_input!(item as ItemFn);
+    export::expand(options, function).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+#[proc_macro]
+pub fn export_error_api(input: TokenStream) -> TokenStream {
+    parse_macro_input!(input as ErrorApiOptions).expand().into()
+}
@@ -0,0 +1,82 @@
+use std::ptr::null;
+use std::sync::{Arc, Mutex, RwLock};
+use crate::error::{set_last_error, ErrorCode};
+use crate::panic::catch_panic;
+use crate::ffi_error::FfiError;
+use crate::util::handle_result;
+use crate::error_stack::ErrorScope;
+
+pub fn arc_to_ptr<T>(object: Arc<T>) -> *const T {
+    Arc::into_raw(object)
+}
+
+pub fn arc_result_to_ptr<T, E: ErrorCode>(context: &'static str, result: Result<Arc<T>, E>) -> *const T {
+    handle_result(context, null(), result.map(arc_to_ptr))
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn arc_clone_ptr<T>(context: &'static str, arc_ptr: *const T) -> *const T {
+    if arc_ptr.is_null() {
+        set_last_error(context, FfiError::NullPointer);
+        return null();
+    }
+    unsafe { Arc::increment_strong_count(arc_ptr) };
+    arc_ptr
+}
+
+pub fn arc_release_ptr<T>(arc_ptr: *const T) -> Result<(), FfiError> {
+    take_arc_ownership(arc_ptr).map(drop)
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn take_arc_ownership<T>(arc_ptr: *const T) -> Result<Arc<T>, FfiError> {
+    if arc_ptr.is_null() {
+        Err(FfiError::NullPointer)
+    } else {
+        Ok(unsafe { Arc::from_raw(arc_ptr) })
+    }
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn with_arc<T, R, F: FnOnce(&T) -> R>(context: &'static str, arc_ptr: *const T, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    if arc_ptr.is_null() {
+        set_last_error(context, FfiError::NullPointer);
+        return error_return_value;
+    }
+    let object = unsafe {
+        Arc::increment_strong_count(arc_ptr);
+        Arc::from_raw(arc_ptr)
+    };
+    catch_panic(context, error_return_value, || f(&object))
+}
+
+pub fn with_arc_lock<T, R, F: FnOnce(&mut T) -> R>(context: &'static str, arc_ptr: *const Mutex<T>, error_return_value: R, f: F) -> R {
+    with_arc(context, arc_ptr, None, |mutex| match mutex.lock() {
+        Ok(mut guard) => Some(f(&mut guard)),
+        Err(_) => {
+            set_last_error(context, FfiError::LockPoisoned);
+            None
+        }
+    }).unwrap_or(error_return_value)
+}
+
+pub fn with_arc_read<T, R, F: FnOnce(&T) -> R>(context: &'static str, arc_ptr: *const RwLock<T>, error_return_value: R, f: F) -> R {
+    with_arc(context, arc_ptr, None, |lock| match lock.read() {
+        Ok(guard) => Some(f(&guard)),
+        Err(_) => {
+            set_last_error(context, FfiError::LockPoisoned);
+            None
+        }
+    }).unwrap_or(error_return_value)
+}
+
+pub fn with_arc_write<T, R, F: FnOnce(&mut T) -> R>(context: &'static str, arc_ptr: *const RwLock<T>, error_return_value: R, f: F) -> R {
+    with_arc(context, arc_ptr, None, |lock| match lock.write() {
+        Ok(mut guard) => Some(f(&mut guard)),
+        Err(_) => {
+            set_last_error(context, FfiError::LockPoisoned);
+            None
+        }
+    }).unwrap_or(error_return_value)
+}
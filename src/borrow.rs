@@ -0,0 +1,67 @@
+#[cfg(feature = "borrow-tracker")]
+use std::collections::BTreeMap;
+use crate::ffi_error::FfiError;
+#[cfg(feature = "borrow-tracker")]
+use std::sync::Mutex;
+
+#[cfg(feature = "borrow-tracker")]
+static BORROWS: Mutex<BTreeMap<usize, isize>> = Mutex::new(BTreeMap::new());
+
+pub(crate) struct BorrowGuard {
+    #[cfg(feature = "borrow-tracker")]
+    address: Option<usize>,
+}
+
+#[cfg(feature = "borrow-tracker")]
+impl BorrowGuard {
+    fn acquire<T>(t_ptr: *const T, exclusive: bool) -> Result<Self, FfiError> {
+        if size_of::<T>() == 0 {
+            return Ok(BorrowGuard { address: None });
+        }
+        let address = t_ptr as usize;
+        let mut borrows = BORROWS.lock().unwrap_or_else(|e| e.into_inner());
+        let count = borrows.entry(address).or_insert(0);
+        match (*count, exclusive) {
+            (0, true) => *count = -1,
+            (0.., false) => *count += 1,
+            (-1, _) => return Err(FfiError::AlreadyBorrowed { mutably: true }),
+            _ => return Err(FfiError::AlreadyBorrowed { mutably: false }),
+        }
+        Ok(BorrowGuard { address: Some(address) })
+    }
+
+    pub(crate) fn exclusive<T>(t_ptr: *const T) -> Result<Self, FfiError> {
+        Self::acquire(t_ptr, true)
+    }
+
+    pub(crate) fn shared<T>(t_ptr: *const T) -> Result<Self, FfiError> {
+        Self::acquire(t_ptr, false)
+    }
+}
+
+#[cfg(feature = "borrow-tracker")]
+impl Drop for BorrowGuard {
+    fn drop(&mut self) {
+        if let Some(address) = self.address {
+            let mut borrows = BORROWS.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(count) = borrows.get_mut(&address) {
+                if *count > 1 {
+                    *count -= 1;
+                } else {
+                    borrows.remove(&address);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "borrow-tracker"))]
+impl BorrowGuard {
+    pub(crate) fn exclusive<T>(_t_ptr: *const T) -> Result<Self, FfiError> {
+        Ok(BorrowGuard {})
+    }
+
+    pub(crate) fn shared<T>(_t_ptr: *const T) -> Result<Self, FfiError> {
+        Ok(BorrowGuard {})
+    }
+}
@@ -0,0 +1,293 @@
+use std::cell::{RefCell, UnsafeCell};
+use std::error::Error;
+use std::ffi::CStr;
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use std::os::raw::{c_char, c_void};
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::error::{set_last_error, ErrorCode, ERROR_CODE_CALLBACK};
+use crate::ffi_error::FfiError;
+use crate::panic::catch_panic;
+use crate::error_stack::ErrorScope;
+
+pub type FreeUserData = extern "C" fn(*mut c_void);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallbackError {
+    pub code: i32,
+    pub message: String,
+}
+
+impl Display for CallbackError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for CallbackError {}
+
+impl ErrorCode for CallbackError {
+    fn error_code(&self) -> i32 {
+        self.code
+    }
+}
+
+thread_local! {
+    static CALLBACK_ERROR: RefCell<Option<CallbackError>> = const { RefCell::new(None) };
+}
+
+pub fn set_callback_error<S: Into<String>>(code: i32, message: S) {
+    let code = if code == 0 { ERROR_CODE_CALLBACK } else { code };
+    CALLBACK_ERROR.with(|e| *e.borrow_mut() = Some(CallbackError { code, message: message.into() }));
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn set_callback_error_from_ptr(code: i32, message: *const c_char) {
+    if message.is_null() {
+        set_callback_error(code, "Callback failed");
+    } else {
+        set_callback_error(code, unsafe { CStr::from_ptr(message) }.to_string_lossy());
+    }
+}
+
+pub fn take_callback_error() -> Option<CallbackError> {
+    CALLBACK_ERROR.with(|e| e.borrow_mut().take())
+}
+
+pub fn clear_callback_error() {
+    CALLBACK_ERROR.with(|e| *e.borrow_mut() = None);
+}
+
+fn check_callback_result<Ret>(ret: Ret, is_error: bool) -> Result<Ret, CallbackError> {
+    let error = take_callback_error();
+    if is_error {
+        Err(error.unwrap_or_else(|| CallbackError { code: ERROR_CODE_CALLBACK, message: "Callback failed".to_string() }))
+    } else {
+        Ok(ret)
+    }
+}
+
+struct ClosureState<F> {
+    in_use: AtomicBool,
+    closure: UnsafeCell<F>,
+}
+
+impl<F> ClosureState<F> {
+    fn call<R>(&self, context: &'static str, error_return_value: R, f: impl FnOnce(&mut F) -> R) -> R {
+        if self.in_use.swap(true, Ordering::Acquire) {
+            set_last_error(context, FfiError::AlreadyBorrowed { mutably: true });
+            return error_return_value;
+        }
+        let result = catch_panic(context, error_return_value, || f(unsafe { &mut *self.closure.get() }));
+        self.in_use.store(false, Ordering::Release);
+        result
+    }
+}
+
+pub trait CallbackArgs<Ret>: Sized {
+    type Function: Copy;
+
+    fn invoke(function: Self::Function, user_data: *mut c_void, args: Self) -> Ret;
+
+    fn trampoline<Closure: FnMut(Self) -> Ret>() -> Self::Function where Ret: Default;
+}
+
+macro_rules! impl_callback_args {
+    ($($arg:ident),*) => {
+        impl<$($arg,)* Ret> CallbackArgs<Ret> for ($($arg,)*) {
+            type Function = extern "C" fn(*mut c_void $(, $arg)*) -> Ret;
+
+            #[allow(non_snake_case)]
+            fn invoke(function: Self::Function, user_data: *mut c_void, ($($arg,)*): Self) -> Ret {
+                function(user_data $(, $arg)*)
+            }
+
+            fn trampoline<Closure: FnMut(Self) -> Ret>() -> Self::Function where Ret: Default {
+                #[allow(non_snake_case)]
+                extern "C" fn trampoline<Closure: FnMut(($($arg,)*)) -> Ret, $($arg,)* Ret: Default>(user_data: *mut c_void $(, $arg: $arg)*) -> Ret {
+                    if user_data.is_null() {
+                        set_last_error("Calling closure", FfiError::NullPointer);
+                        return Ret::default();
+                    }
+                    let state = unsafe { &*(user_data as *const ClosureState<Closure>) };
+                    state.call("Calling closure", Ret::default(), |f| f(($($arg,)*)))
+                }
+                trampoline::<Closure, $($arg,)* Ret>
+            }
+        }
+    };
+}
+
+impl_callback_args!();
+impl_callback_args!(A);
+impl_callback_args!(A, B);
+impl_callback_args!(A, B, C);
+impl_callback_args!(A, B, C, D);
+impl_callback_args!(A, B, C, D, E);
+impl_callback_args!(A, B, C, D, E, F);
+impl_callback_args!(A, B, C, D, E, F, G);
+impl_callback_args!(A, B, C, D, E, F, G, H);
+
+pub struct CCallback<Args: CallbackArgs<Ret>, Ret = ()> {
+    function: Args::Function,
+    user_data: *mut c_void,
+    _marker: PhantomData<fn(Args) -> Ret>,
+}
+
+impl<Args: CallbackArgs<Ret>, Ret> Clone for CCallback<Args, Ret> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Args: CallbackArgs<Ret>, Ret> Copy for CCallback<Args, Ret> {}
+
+impl<Args: CallbackArgs<Ret>, Ret> CCallback<Args, Ret> {
+    pub fn new(function: Args::Function, user_data: *mut c_void) -> Self {
+        CCallback { function, user_data, _marker: PhantomData }
+    }
+
+    pub fn from_raw(function: Option<Args::Function>, user_data: *mut c_void) -> Option<Self> {
+        function.map(|function| Self::new(function, user_data))
+    }
+
+    pub fn function(&self) -> Args::Function {
+        self.function
+    }
+
+    pub fn user_data(&self) -> *mut c_void {
+        self.user_data
+    }
+
+    pub fn call(&self, args: Args) -> Ret {
+        Args::invoke(self.function, self.user_data, args)
+    }
+
+    pub fn call_checked(&self, args: Args, error_return_value: Ret) -> Result<Ret, CallbackError> where Ret: PartialEq {
+        self.call_checked_with(args, |ret| *ret == error_return_value)
+    }
+
+    pub fn call_checked_with<F: FnOnce(&Ret) -> bool>(&self, args: Args, is_error: F) -> Result<Ret, CallbackError> {
+        clear_callback_error();
+        let ret = self.call(args);
+        let failed = is_error(&ret);
+        check_callback_result(ret, failed)
+    }
+
+    /// # Safety
+    ///
+    /// The caller must know that the C side allows the function to be called, and the user data to be used, from other threads.
+    pub unsafe fn assume_send(self) -> SendCallback<Args, Ret> {
+        SendCallback(self)
+    }
+}
+
+pub struct SendCallback<Args: CallbackArgs<Ret>, Ret = ()>(CCallback<Args, Ret>);
+
+unsafe impl<Args: CallbackArgs<Ret>, Ret> Send for SendCallback<Args, Ret> {}
+
+impl<Args: CallbackArgs<Ret>, Ret> Clone for SendCallback<Args, Ret> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Args: CallbackArgs<Ret>, Ret> Copy for SendCallback<Args, Ret> {}
+
+impl<Args: CallbackArgs<Ret>, Ret> SendCallback<Args, Ret> {
+    pub fn call(&self, args: Args) -> Ret {
+        self.0.call(args)
+    }
+
+    pub fn call_checked(&self, args: Args, error_return_value: Ret) -> Result<Ret, CallbackError> where Ret: PartialEq {
+        self.0.call_checked(args, error_return_value)
+    }
+
+    pub fn call_checked_with<F: FnOnce(&Ret) -> bool>(&self, args: Args, is_error: F) -> Result<Ret, CallbackError> {
+        self.0.call_checked_with(args, is_error)
+    }
+
+    pub fn into_inner(self) -> CCallback<Args, Ret> {
+        self.0
+    }
+}
+
+pub fn with_callback<Args: CallbackArgs<Ret>, Ret, R, F: FnOnce(CCallback<Args, Ret>) -> R>(context: &'static str, function: Option<Args::Function>, user_data: *mut c_void, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    match CCallback::from_raw(function, user_data) {
+        Some(callback) => catch_panic(context, error_return_value, || f(callback)),
+        None => {
+            set_last_error(context, FfiError::NullPointer);
+            error_return_value
+        }
+    }
+}
+
+pub fn closure_to_callback<Args: CallbackArgs<Ret>, Ret: Default, F: FnMut(Args) -> Ret + Send + 'static>(f: F) -> (Args::Function, *mut c_void, FreeUserData) {
+    extern "C" fn free<F>(user_data: *mut c_void) {
+        if !user_data.is_null() {
+            drop(unsafe { Box::from_raw(user_data as *mut ClosureState<F>) });
+        }
+    }
+    let state = ClosureState { in_use: AtomicBool::new(false), closure: UnsafeCell::new(f) };
+    (Args::trampoline::<F>(), Box::into_raw(Box::new(state)) as *mut c_void, free::<F>)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::error::{get_last_error_code, get_last_error_message, TestSettings, ERROR_CODE_ALREADY_BORROWED, ERROR_CODE_PANIC};
+
+    #[test]
+    fn closure_round_trip() {
+        let mut total = 0;
+        let (function, user_data, free) = closure_to_callback(move |(a, b): (i32, i32)| {
+            total += a + b;
+            total
+        });
+        let callback = CCallback::<(i32, i32), i32>::new(function, user_data);
+        assert_eq!(callback.call((1, 2)), 3);
+        assert_eq!(callback.call((3, 4)), 10);
+        free(user_data);
+    }
+
+    #[test]
+    fn closure_panic_is_caught() {
+        let _settings = TestSettings::lock();
+        let (function, user_data, free) = closure_to_callback(|(): ()| -> i32 { panic!("closure failed") });
+        assert_eq!(CCallback::<(), i32>::new(function, user_data).call(()), 0);
+        assert_eq!(get_last_error_code(), ERROR_CODE_PANIC);
+        assert_eq!(get_last_error_message(), "Panicked: closure failed");
+        assert_eq!(CCallback::<(), i32>::new(function, user_data).call(()), 0);
+        free(user_data);
+    }
+
+    #[test]
+    fn free_drops_the_closure() {
+        let captured = Arc::new(());
+        let inner = captured.clone();
+        let (_, user_data, free) = closure_to_callback(move |(): ()| drop(inner.clone()));
+        assert_eq!(Arc::strong_count(&captured), 2);
+        free(user_data);
+        assert_eq!(Arc::strong_count(&captured), 1);
+    }
+
+    #[test]
+    fn reentrant_call_is_rejected() {
+        thread_local! {
+            static CALLBACK: RefCell<Option<CCallback<(u8,), u8>>> = const { RefCell::new(None) };
+        }
+        let (function, user_data, free) = closure_to_callback(|(depth,): (u8,)| -> u8 {
+            match depth {
+                0 => CALLBACK.with(|callback| callback.borrow().unwrap()).call((1,)) + 10,
+                _ => 1,
+            }
+        });
+        CALLBACK.with(|callback| *callback.borrow_mut() = Some(CCallback::new(function, user_data)));
+        assert_eq!(CCallback::<(u8,), u8>::new(function, user_data).call((0,)), 10);
+        assert_eq!(get_last_error_code(), ERROR_CODE_ALREADY_BORROWED);
+        assert_eq!(CCallback::<(u8,), u8>::new(function, user_data).call((1,)), 1);
+        free(user_data);
+    }
+}
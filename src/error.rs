@@ -1,14 +1,395 @@
+use std::backtrace::Backtrace;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::ffi::{NulError, IntoStringError};
 use std::fmt::Display;
-use std::cell::RefCell;
+use std::os::raw::c_char;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::str::Utf8Error;
+use std::string::FromUtf8Error;
+use crate::error_stack::push_error_frame;
+use crate::util::string_to_ptr;
+
+pub const ERROR_CODE_NONE: i32 = 0;
+pub const ERROR_CODE_UNKNOWN: i32 = 1;
+pub const ERROR_CODE_NULL_POINTER: i32 = 2;
+pub const ERROR_CODE_INVALID_UTF8: i32 = 3;
+pub const ERROR_CODE_INDEX_OUT_OF_BOUNDS: i32 = 4;
+pub const ERROR_CODE_INTERIOR_NUL: i32 = 5;
+pub const ERROR_CODE_PANIC: i32 = 6;
+pub const ERROR_CODE_INVALID_HANDLE: i32 = 7;
+pub const ERROR_CODE_TYPE_MISMATCH: i32 = 8;
+pub const ERROR_CODE_INVALID_ARGUMENT: i32 = 9;
+pub const ERROR_CODE_BUFFER_TOO_SMALL: i32 = 10;
+pub const ERROR_CODE_INVALID_UTF16: i32 = 11;
+pub const ERROR_CODE_CALLBACK: i32 = 12;
+pub const ERROR_CODE_LOCK_POISONED: i32 = 13;
+pub const ERROR_CODE_ALREADY_BORROWED: i32 = 14;
+
+pub trait ErrorCode: Display {
+    fn error_code(&self) -> i32 {
+        ERROR_CODE_UNKNOWN
+    }
+
+    fn error_source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+#[macro_export]
+macro_rules! impl_error_code {
+    ($ty:ty) => {
+        $crate::impl_error_code!($ty, |_error| $crate::ERROR_CODE_UNKNOWN);
+    };
+    ($ty:ty, |$error:ident| $code:expr) => {
+        impl $crate::ErrorCode for $ty {
+            fn error_code(&self) -> i32 {
+                let $error = self;
+                $code
+            }
+
+            fn error_source(&self) -> Option<&(dyn ::std::error::Error + 'static)> {
+                ::std::error::Error::source(self)
+            }
+        }
+    };
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodedError<E> {
+    pub code: i32,
+    pub error: E,
+}
+
+impl<E: Display> CodedError<E> {
+    pub fn new(code: i32, error: E) -> Self {
+        CodedError { code, error }
+    }
+
+    pub fn unknown(error: E) -> Self {
+        CodedError { code: ERROR_CODE_UNKNOWN, error }
+    }
+}
+
+impl<E: Display> Display for CodedError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<E: Display> ErrorCode for CodedError<E> {
+    fn error_code(&self) -> i32 {
+        self.code
+    }
+}
+
+impl ErrorCode for &str {}
+impl ErrorCode for String {}
+impl ErrorCode for &String {}
+impl ErrorCode for Cow<'_, str> {}
+
+impl ErrorCode for &(dyn Error + 'static) {
+    fn error_source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source()
+    }
+}
+
+impl ErrorCode for Box<dyn Error> {
+    fn error_source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source()
+    }
+}
+
+impl ErrorCode for Box<dyn Error + Send> {
+    fn error_source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source()
+    }
+}
+
+impl ErrorCode for Box<dyn Error + Send + Sync> {
+    fn error_source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source()
+    }
+}
+
+impl ErrorCode for std::io::Error {
+    fn error_source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source()
+    }
+}
+
+impl ErrorCode for std::fmt::Error {}
+impl ErrorCode for std::num::ParseIntError {}
+impl ErrorCode for std::num::ParseFloatError {}
+
+impl ErrorCode for NulError {
+    fn error_code(&self) -> i32 {
+        ERROR_CODE_INTERIOR_NUL
+    }
+}
+
+impl ErrorCode for Utf8Error {
+    fn error_code(&self) -> i32 {
+        ERROR_CODE_INVALID_UTF8
+    }
+}
+
+impl ErrorCode for FromUtf8Error {
+    fn error_code(&self) -> i32 {
+        ERROR_CODE_INVALID_UTF8
+    }
+}
+
+impl ErrorCode for IntoStringError {
+    fn error_code(&self) -> i32 {
+        ERROR_CODE_INVALID_UTF8
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LastError {
+    pub code: i32,
+    pub context: &'static str,
+    pub message: String,
+    pub sources: Vec<String>,
+    pub backtrace: Option<String>,
+    pub sequence: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ErrorCapture {
+    pub source_chain: bool,
+    pub backtrace: bool,
+}
+
+impl Default for ErrorCapture {
+    fn default() -> Self {
+        ErrorCapture { source_chain: true, backtrace: false }
+    }
+}
+
+impl Display for LastError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Error {}: {}", self.context, self.message)
+    }
+}
+
+static CAPTURE_SOURCE_CHAIN: AtomicBool = AtomicBool::new(true);
+static CAPTURE_BACKTRACE: AtomicBool = AtomicBool::new(false);
+static AUTO_CLEAR_LAST_ERROR: AtomicBool = AtomicBool::new(false);
 
 thread_local! {
-    static LAST_ERROR: RefCell<String> = RefCell::new(String::new());
+    static LAST_ERROR: RefCell<Option<LastError>> = const { RefCell::new(None) };
+    static ERROR_SEQUENCE: Cell<u64> = const { Cell::new(0) };
+}
+
+pub fn set_error_capture(capture: ErrorCapture) {
+    CAPTURE_SOURCE_CHAIN.store(capture.source_chain, Ordering::Relaxed);
+    CAPTURE_BACKTRACE.store(capture.backtrace, Ordering::Relaxed);
+}
+
+pub fn get_error_capture() -> ErrorCapture {
+    ErrorCapture {
+        source_chain: CAPTURE_SOURCE_CHAIN.load(Ordering::Relaxed),
+        backtrace: CAPTURE_BACKTRACE.load(Ordering::Relaxed),
+    }
+}
+
+fn capture_backtrace() -> Option<String> {
+    if CAPTURE_BACKTRACE.load(Ordering::Relaxed) {
+        Some(Backtrace::force_capture().to_string())
+    } else {
+        None
+    }
+}
+
+pub fn set_last_error<E: ErrorCode>(context: &'static str, error: E) {
+    let mut sources = Vec::new();
+    if CAPTURE_SOURCE_CHAIN.load(Ordering::Relaxed) {
+        let mut source = error.error_source();
+        while let Some(e) = source {
+            sources.push(e.to_string());
+            source = e.source();
+        }
+    }
+    set_last_error_record(LastError {
+        code: error.error_code(),
+        context,
+        message: error.to_string(),
+        sources,
+        backtrace: capture_backtrace(),
+        sequence: 0,
+    });
 }
 
-pub fn set_last_error<E: Display>(context: &'static str, error: E) {
-    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = format!("Error {}: {}", context, error));
+pub fn set_last_error_with_code<E: Display>(context: &'static str, code: i32, error: E) {
+    set_last_error_record(LastError {
+        code,
+        context,
+        message: error.to_string(),
+        sources: Vec::new(),
+        backtrace: capture_backtrace(),
+        sequence: 0,
+    });
+}
+
+pub fn set_last_error_record(mut record: LastError) {
+    if record.code == ERROR_CODE_NONE {
+        record.code = ERROR_CODE_UNKNOWN;
+    }
+    record.sequence = ERROR_SEQUENCE.with(|sequence| {
+        sequence.set(sequence.get() + 1);
+        sequence.get()
+    });
+    push_error_frame(&record);
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(record));
+}
+
+pub fn set_auto_clear_last_error(enabled: bool) {
+    AUTO_CLEAR_LAST_ERROR.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_auto_clear_last_error() -> bool {
+    AUTO_CLEAR_LAST_ERROR.load(Ordering::Relaxed)
+}
+
+pub fn clear_last_error() {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = None);
+}
+
+pub fn take_last_error() -> Option<LastError> {
+    LAST_ERROR.with(|last_error| last_error.borrow_mut().take())
+}
+
+pub fn has_last_error() -> bool {
+    LAST_ERROR.with(|last_error| last_error.borrow().is_some())
+}
+
+pub fn peek_last_error<R, F: FnOnce(Option<&LastError>) -> R>(f: F) -> R {
+    LAST_ERROR.with(|last_error| f(last_error.borrow().as_ref()))
+}
+
+pub fn get_last_error_sequence() -> u64 {
+    ERROR_SEQUENCE.with(Cell::get)
 }
 
 pub fn get_last_error() -> String {
+    LAST_ERROR.with(|it| it.borrow().as_ref().map(ToString::to_string).unwrap_or_default())
+}
+
+pub fn get_last_error_record() -> Option<LastError> {
     LAST_ERROR.with(|it| it.borrow().clone())
 }
+
+pub fn get_last_error_code() -> i32 {
+    LAST_ERROR.with(|it| it.borrow().as_ref().map_or(ERROR_CODE_NONE, |e| e.code))
+}
+
+pub fn get_last_error_context() -> &'static str {
+    LAST_ERROR.with(|it| it.borrow().as_ref().map_or("", |e| e.context))
+}
+
+pub fn get_last_error_message() -> String {
+    LAST_ERROR.with(|it| it.borrow().as_ref().map(|e| e.message.clone()).unwrap_or_default())
+}
+
+pub fn get_last_error_sources() -> Vec<String> {
+    LAST_ERROR.with(|it| it.borrow().as_ref().map(|e| e.sources.clone()).unwrap_or_default())
+}
+
+pub fn get_last_error_source_count() -> usize {
+    LAST_ERROR.with(|it| it.borrow().as_ref().map_or(0, |e| e.sources.len()))
+}
+
+pub fn get_last_error_source(index: usize) -> Option<String> {
+    LAST_ERROR.with(|it| it.borrow().as_ref().and_then(|e| e.sources.get(index).cloned()))
+}
+
+pub fn get_last_error_backtrace() -> Option<String> {
+    LAST_ERROR.with(|it| it.borrow().as_ref().and_then(|e| e.backtrace.clone()))
+}
+
+pub fn last_error_to_ptr() -> *mut c_char {
+    match get_last_error_record() {
+        Some(last_error) => string_to_ptr("Getting last error", last_error.to_string().replace('\0', "")),
+        None => null_mut(),
+    }
+}
+
+pub fn last_error_context_to_ptr() -> *mut c_char {
+    match get_last_error_record() {
+        Some(last_error) => string_to_ptr("Getting last error context", last_error.context.replace('\0', "")),
+        None => null_mut(),
+    }
+}
+
+pub fn last_error_message_to_ptr() -> *mut c_char {
+    match get_last_error_record() {
+        Some(last_error) => string_to_ptr("Getting last error message", last_error.message.replace('\0', "")),
+        None => null_mut(),
+    }
+}
+
+pub fn last_error_source_to_ptr(index: usize) -> *mut c_char {
+    match get_last_error_source(index) {
+        Some(source) => string_to_ptr("Getting last error source", source.replace('\0', "")),
+        None => null_mut(),
+    }
+}
+
+pub fn last_error_backtrace_to_ptr() -> *mut c_char {
+    match get_last_error_backtrace() {
+        Some(backtrace) => string_to_ptr("Getting last error backtrace", backtrace.replace('\0', "")),
+        None => null_mut(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) struct TestSettings {
+    _lock: std::sync::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+impl TestSettings {
+    pub(crate) fn lock() -> Self {
+        static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+        TestSettings { _lock: LOCK.lock().unwrap_or_else(|e| e.into_inner()) }
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestSettings {
+    fn drop(&mut self) {
+        set_auto_clear_last_error(false);
+        crate::error_stack::set_error_stack_enabled(false);
+        crate::panic::set_panic_formatter(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::FfiStatus;
+    use crate::util::{handle_result, take_string_ownership};
+
+    #[test]
+    fn code_zero_is_recorded_as_unknown() {
+        handle_result("Failing", (), Err(CodedError::new(ERROR_CODE_NONE, "failed")));
+        assert!(has_last_error());
+        assert_eq!(get_last_error_code(), ERROR_CODE_UNKNOWN);
+        handle_result("Failing", (), Err(FfiStatus::OK));
+        assert_eq!(get_last_error_code(), ERROR_CODE_UNKNOWN);
+    }
+
+    #[test]
+    fn context_and_message_to_ptr() {
+        clear_last_error();
+        assert!(last_error_context_to_ptr().is_null());
+        assert!(last_error_message_to_ptr().is_null());
+        handle_result("Doing something", (), Err("it failed"));
+        assert_eq!(take_string_ownership(last_error_context_to_ptr()).unwrap().to_str(), Ok("Doing something"));
+        assert_eq!(take_string_ownership(last_error_message_to_ptr()).unwrap().to_str(), Ok("it failed"));
+    }
+}
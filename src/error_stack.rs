@@ -0,0 +1,131 @@
+use std::cell::{Cell, RefCell};
+use std::os::raw::c_char;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::error::{clear_last_error, get_last_error_sequence, is_auto_clear_last_error, LastError};
+use crate::util::string_to_ptr;
+
+pub const MAX_ERROR_STACK_DEPTH: usize = 64;
+
+static ERROR_STACK_ENABLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static ERROR_STACK: RefCell<Vec<LastError>> = const { RefCell::new(Vec::new()) };
+    static SCOPE_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+pub(crate) struct ErrorScope;
+
+impl ErrorScope {
+    pub(crate) fn enter() -> Self {
+        let depth = SCOPE_DEPTH.with(|depth| depth.replace(depth.get() + 1));
+        if depth == 0 {
+            if is_auto_clear_last_error() {
+                clear_last_error();
+            }
+            if is_error_stack_enabled() {
+                clear_error_stack();
+            }
+        }
+        ErrorScope
+    }
+}
+
+impl Drop for ErrorScope {
+    fn drop(&mut self) {
+        SCOPE_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+pub(crate) fn push_error_frame(record: &LastError) {
+    if !is_error_stack_enabled() {
+        return;
+    }
+    ERROR_STACK.with(|stack| {
+        let mut stack = stack.borrow_mut();
+        if stack.len() >= MAX_ERROR_STACK_DEPTH {
+            stack.remove(1);
+        }
+        stack.push(record.clone());
+    });
+}
+
+pub(crate) fn push_context_frame(context: &'static str, sequence: u64) {
+    if !is_error_stack_enabled() || get_last_error_sequence() == sequence {
+        return;
+    }
+    let frame = ERROR_STACK.with(|stack| match stack.borrow().last() {
+        Some(frame) if frame.context != context => Some(LastError { context, ..frame.clone() }),
+        _ => None,
+    });
+    if let Some(frame) = frame {
+        push_error_frame(&frame);
+    }
+}
+
+pub fn set_error_stack_enabled(enabled: bool) {
+    ERROR_STACK_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_error_stack_enabled() -> bool {
+    ERROR_STACK_ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn clear_error_stack() {
+    ERROR_STACK.with(|stack| stack.borrow_mut().clear());
+}
+
+pub fn get_error_stack() -> Vec<LastError> {
+    ERROR_STACK.with(|stack| stack.borrow().iter().rev().cloned().collect())
+}
+
+pub fn get_error_stack_depth() -> usize {
+    ERROR_STACK.with(|stack| stack.borrow().len())
+}
+
+pub fn get_error_stack_frame(index: usize) -> Option<LastError> {
+    ERROR_STACK.with(|stack| stack.borrow().iter().rev().nth(index).cloned())
+}
+
+pub fn error_stack_frame_to_ptr(index: usize) -> *mut c_char {
+    match get_error_stack_frame(index) {
+        Some(frame) => string_to_ptr("Getting error stack frame", frame.to_string().replace('\0', "")),
+        None => null_mut(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::TestSettings;
+    use crate::util::{handle_result, with};
+
+    #[test]
+    fn context_frames_on_the_way_out() {
+        let _settings = TestSettings::lock();
+        set_error_stack_enabled(true);
+        let mut object = 1;
+        let result = with("Outer", &mut object, -1, |_| handle_result("Inner", -1, Err("root")));
+        assert_eq!(result, -1);
+        let contexts: Vec<_> = get_error_stack().iter().map(|frame| frame.context).collect();
+        assert_eq!(contexts, ["Outer", "Inner"]);
+        assert_eq!(get_error_stack_frame(0).map(|frame| frame.message), Some("root".to_string()));
+
+        with("Outer", &mut object, -1, |_| handle_result("Outer", -1, Err("root")));
+        assert_eq!(get_error_stack_depth(), 1);
+    }
+
+    #[test]
+    fn cleared_when_the_next_call_starts() {
+        let _settings = TestSettings::lock();
+        set_error_stack_enabled(true);
+        clear_error_stack();
+        for _ in 0..MAX_ERROR_STACK_DEPTH * 2 {
+            handle_result("Failing", (), Err("failed"));
+        }
+        assert_eq!(get_error_stack_depth(), MAX_ERROR_STACK_DEPTH);
+        let mut object = 1;
+        with("Succeeding", &mut object, (), |_| ());
+        assert_eq!(get_error_stack_depth(), 0);
+    }
+}
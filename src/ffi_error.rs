@@ -0,0 +1,102 @@
+use std::error::Error;
+use std::ffi::NulError;
+use std::fmt::{Display, Formatter};
+use std::str::Utf8Error;
+use crate::error::*;
+use crate::status::FfiStatus;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FfiError {
+    NullPointer,
+    MisalignedPointer,
+    InvalidUtf8 { offset: usize },
+    InvalidUtf16 { index: usize },
+    InteriorNul { position: usize },
+    IndexOutOfBounds { index: usize, len: usize },
+    LengthTooLarge { len: usize },
+    InvalidBuffer { len: usize, cap: usize },
+    BufferTooSmall { required: usize, available: usize },
+    InvalidHandle,
+    WrongHandleType,
+    HandleFreed,
+    StaleHandle,
+    HandleInUse,
+    RegistryFull,
+    LockPoisoned,
+    AlreadyBorrowed { mutably: bool },
+    NotTagged { expected: &'static str },
+    AlreadyFreed { expected: &'static str },
+    TypeMismatch { expected: &'static str, found: &'static str },
+}
+
+impl FfiError {
+    pub fn status(&self) -> FfiStatus {
+        FfiStatus(self.error_code())
+    }
+}
+
+impl Display for FfiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FfiError::NullPointer => write!(f, "Invalid pointer"),
+            FfiError::MisalignedPointer => write!(f, "Misaligned pointer"),
+            FfiError::InvalidUtf8 { offset } => write!(f, "Invalid UTF-8 in string at byte offset {}", offset),
+            FfiError::InvalidUtf16 { index } => write!(f, "Invalid UTF-16 string: unpaired surrogate at index {}", index),
+            FfiError::InteriorNul { position } => write!(f, "String contains a nul character at position {}", position),
+            FfiError::IndexOutOfBounds { index, len } => write!(f, "Index {} is out of bounds for length {}", index, len),
+            FfiError::LengthTooLarge { len } => write!(f, "Slice length {} is too large", len),
+            FfiError::InvalidBuffer { len, cap } => write!(f, "Invalid buffer: length {} is greater than capacity {}", len, cap),
+            FfiError::BufferTooSmall { required, available } => write!(f, "Buffer too small: {} bytes required, {} available", required, available),
+            FfiError::InvalidHandle => write!(f, "Invalid handle"),
+            FfiError::WrongHandleType => write!(f, "Handle belongs to a different type of object"),
+            FfiError::HandleFreed => write!(f, "Handle has already been freed"),
+            FfiError::StaleHandle => write!(f, "Stale handle"),
+            FfiError::HandleInUse => write!(f, "Handle is in use"),
+            FfiError::RegistryFull => write!(f, "Handle registry is full"),
+            FfiError::LockPoisoned => write!(f, "Lock is poisoned"),
+            FfiError::AlreadyBorrowed { mutably: false } => write!(f, "Object is already borrowed"),
+            FfiError::AlreadyBorrowed { mutably: true } => write!(f, "Object is already mutably borrowed"),
+            FfiError::NotTagged { expected } => write!(f, "Pointer is not a tagged object (expected `{}`)", expected),
+            FfiError::AlreadyFreed { expected } => write!(f, "Object has already been freed (expected `{}`)", expected),
+            FfiError::TypeMismatch { expected, found } => write!(f, "Wrong object type: expected `{}`, found `{}`", expected, found),
+        }
+    }
+}
+
+impl Error for FfiError {}
+
+impl ErrorCode for FfiError {
+    fn error_code(&self) -> i32 {
+        match self {
+            FfiError::NullPointer => ERROR_CODE_NULL_POINTER,
+            FfiError::MisalignedPointer | FfiError::LengthTooLarge { .. } | FfiError::InvalidBuffer { .. } => ERROR_CODE_INVALID_ARGUMENT,
+            FfiError::InvalidUtf8 { .. } => ERROR_CODE_INVALID_UTF8,
+            FfiError::InvalidUtf16 { .. } => ERROR_CODE_INVALID_UTF16,
+            FfiError::InteriorNul { .. } => ERROR_CODE_INTERIOR_NUL,
+            FfiError::IndexOutOfBounds { .. } => ERROR_CODE_INDEX_OUT_OF_BOUNDS,
+            FfiError::BufferTooSmall { .. } => ERROR_CODE_BUFFER_TOO_SMALL,
+            FfiError::InvalidHandle | FfiError::HandleFreed | FfiError::StaleHandle | FfiError::HandleInUse | FfiError::RegistryFull => ERROR_CODE_INVALID_HANDLE,
+            FfiError::WrongHandleType | FfiError::NotTagged { .. } | FfiError::AlreadyFreed { .. } | FfiError::TypeMismatch { .. } => ERROR_CODE_TYPE_MISMATCH,
+            FfiError::LockPoisoned => ERROR_CODE_LOCK_POISONED,
+            FfiError::AlreadyBorrowed { .. } => ERROR_CODE_ALREADY_BORROWED,
+        }
+    }
+}
+
+impl From<FfiError> for FfiStatus {
+    fn from(error: FfiError) -> Self {
+        error.status()
+    }
+}
+
+impl From<NulError> for FfiError {
+    fn from(error: NulError) -> Self {
+        FfiError::InteriorNul { position: error.nul_position() }
+    }
+}
+
+impl From<Utf8Error> for FfiError {
+    fn from(error: Utf8Error) -> Self {
+        FfiError::InvalidUtf8 { offset: error.valid_up_to() }
+    }
+}
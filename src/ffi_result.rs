@@ -0,0 +1,88 @@
+use std::os::raw::c_char;
+use std::ptr::null_mut;
+use crate::error::{get_last_error_record, ErrorCode, ERROR_CODE_NONE, ERROR_CODE_UNKNOWN};
+use crate::util::{handle_result, string_to_ptr, take_string_ownership};
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct FfiResult<T> {
+    pub status: i32,
+    pub value: T,
+    pub error: *mut c_char,
+}
+
+impl<T> FfiResult<T> {
+    pub fn ok(value: T) -> Self {
+        FfiResult { status: ERROR_CODE_NONE, value, error: null_mut() }
+    }
+
+    pub fn from_last_error(error_return_value: T) -> Self {
+        match get_last_error_record() {
+            Some(last_error) => FfiResult {
+                status: if last_error.code == ERROR_CODE_NONE { ERROR_CODE_UNKNOWN } else { last_error.code },
+                value: error_return_value,
+                error: string_to_ptr("Creating result", last_error.to_string().replace('\0', "")),
+            },
+            None => FfiResult { status: ERROR_CODE_UNKNOWN, value: error_return_value, error: null_mut() },
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.status == ERROR_CODE_NONE
+    }
+
+    pub fn free_error(&mut self) {
+        let _ = take_string_ownership(self.error);
+        self.error = null_mut();
+    }
+}
+
+pub fn handle_ffi_result<T, E: ErrorCode>(context: &'static str, error_return_value: T, result: Result<T, E>) -> FfiResult<T> {
+    match handle_result(context, None, result.map(Some)) {
+        Some(value) => FfiResult::ok(value),
+        None => FfiResult::from_last_error(error_return_value),
+    }
+}
+
+pub fn result_to_ffi_result<T: Default, E: ErrorCode>(context: &'static str, result: Result<T, E>) -> FfiResult<T> {
+    handle_ffi_result(context, T::default(), result)
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn free_ffi_result_error<T>(result: *mut FfiResult<T>) {
+    if let Some(result) = unsafe { result.as_mut() } {
+        result.free_error();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{CodedError, ERROR_CODE_INVALID_ARGUMENT};
+
+    #[test]
+    fn ok_result() {
+        let mut result = handle_ffi_result("Getting value", -1, Ok::<_, &str>(5));
+        assert!(result.is_ok());
+        assert_eq!(result.value, 5);
+        assert!(result.error.is_null());
+        result.free_error();
+    }
+
+    #[test]
+    fn error_result() {
+        let result = handle_ffi_result("Getting value", -1, Err(CodedError::new(ERROR_CODE_INVALID_ARGUMENT, "bad")));
+        assert_eq!(result.status, ERROR_CODE_INVALID_ARGUMENT);
+        assert_eq!(result.value, -1);
+        assert_eq!(take_string_ownership(result.error).unwrap().to_str(), Ok("Error Getting value: bad"));
+    }
+
+    #[test]
+    fn code_zero_is_not_ok() {
+        let mut result = handle_ffi_result("Getting value", -1, Err(CodedError::new(ERROR_CODE_NONE, "failed")));
+        assert!(!result.is_ok());
+        assert_eq!(result.status, ERROR_CODE_UNKNOWN);
+        assert!(!result.error.is_null());
+        result.free_error();
+    }
+}
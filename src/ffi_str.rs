@@ -0,0 +1,50 @@
+use std::ffi::CString;
+use std::fmt::{Debug, Formatter};
+use std::os::raw::c_char;
+use std::ptr::null;
+use std::sync::Mutex;
+use crate::error::set_last_error;
+use crate::ffi_error::FfiError;
+
+#[derive(Default)]
+pub struct FfiStr {
+    cache: Mutex<Option<CString>>,
+}
+
+impl FfiStr {
+    pub const fn new() -> Self {
+        FfiStr { cache: Mutex::new(None) }
+    }
+
+    pub fn to_ptr<S: AsRef<[u8]>>(&self, context: &'static str, value: S) -> *const c_char {
+        let value = value.as_ref();
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(cached) = cache.as_ref().filter(|cached| cached.as_bytes() == value) {
+            return cached.as_ptr();
+        }
+        match CString::new(value) {
+            Ok(cached) => cache.insert(cached).as_ptr(),
+            Err(e) => {
+                set_last_error(context, FfiError::from(e));
+                null()
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self.cache.get_mut().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+impl Clone for FfiStr {
+    fn clone(&self) -> Self {
+        FfiStr::new()
+    }
+}
+
+impl Debug for FfiStr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        f.debug_struct("FfiStr").field("cache", &*cache).finish()
+    }
+}
@@ -0,0 +1,329 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use crate::error::{set_last_error, ErrorCode};
+use crate::ffi_error::FfiError;
+use crate::panic::catch_panic;
+use crate::util::handle_result;
+use crate::error_stack::ErrorScope;
+
+pub type FfiHandle = u64;
+
+pub const NULL_HANDLE: FfiHandle = 0;
+
+const INDEX_BITS: u32 = 24;
+const GENERATION_BITS: u32 = 24;
+const INDEX_MASK: u64 = (1 << INDEX_BITS) - 1;
+const GENERATION_MASK: u32 = (1 << GENERATION_BITS) - 1;
+
+static NEXT_REGISTRY_ID: AtomicU16 = AtomicU16::new(1);
+
+thread_local! {
+    static IN_USE: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+type HandleObject<T> = Arc<Mutex<Option<T>>>;
+
+struct Slot<T> {
+    generation: u32,
+    object: Option<HandleObject<T>>,
+}
+
+struct InUse(usize);
+
+impl InUse {
+    fn enter<T>(object: &HandleObject<T>) -> Result<Self, FfiError> {
+        if is_in_use(object) {
+            return Err(FfiError::HandleInUse);
+        }
+        let address = Arc::as_ptr(object) as usize;
+        IN_USE.with(|in_use| in_use.borrow_mut().push(address));
+        Ok(InUse(address))
+    }
+}
+
+impl Drop for InUse {
+    fn drop(&mut self) {
+        IN_USE.with(|in_use| {
+            let mut in_use = in_use.borrow_mut();
+            if let Some(index) = in_use.iter().rposition(|&address| address == self.0) {
+                in_use.remove(index);
+            }
+        });
+    }
+}
+
+fn is_in_use<T>(object: &HandleObject<T>) -> bool {
+    let address = Arc::as_ptr(object) as usize;
+    IN_USE.with(|in_use| in_use.borrow().contains(&address))
+}
+
+struct Slots<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<usize>,
+    len: usize,
+}
+
+pub struct HandleRegistry<T> {
+    id: AtomicU16,
+    slots: Mutex<Slots<T>>,
+}
+
+impl<T> Default for HandleRegistry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> HandleRegistry<T> {
+    pub const fn new() -> Self {
+        HandleRegistry {
+            id: AtomicU16::new(0),
+            slots: Mutex::new(Slots { slots: Vec::new(), free: Vec::new(), len: 0 }),
+        }
+    }
+
+    fn id(&self) -> u16 {
+        let id = self.id.load(Ordering::Acquire);
+        if id != 0 {
+            return id;
+        }
+        let mut new_id = NEXT_REGISTRY_ID.fetch_add(1, Ordering::Relaxed);
+        if new_id == 0 {
+            new_id = NEXT_REGISTRY_ID.fetch_add(1, Ordering::Relaxed);
+        }
+        match self.id.compare_exchange(0, new_id, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => new_id,
+            Err(id) => id,
+        }
+    }
+
+    fn slots(&self) -> MutexGuard<'_, Slots<T>> {
+        self.slots.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn encode(&self, index: usize, generation: u32) -> FfiHandle {
+        (u64::from(self.id()) << (INDEX_BITS + GENERATION_BITS)) | (u64::from(generation) << INDEX_BITS) | index as u64
+    }
+
+    fn decode(&self, handle: FfiHandle) -> Result<(usize, u32), FfiError> {
+        if handle == NULL_HANDLE {
+            return Err(FfiError::InvalidHandle);
+        }
+        if (handle >> (INDEX_BITS + GENERATION_BITS)) as u16 != self.id() {
+            return Err(FfiError::WrongHandleType);
+        }
+        Ok(((handle & INDEX_MASK) as usize, (handle >> INDEX_BITS) as u32 & GENERATION_MASK))
+    }
+
+    fn check_slot(slots: &mut Slots<T>, index: usize, generation: u32) -> Result<&mut Slot<T>, FfiError> {
+        let slot = slots.slots.get_mut(index).ok_or(FfiError::InvalidHandle)?;
+        if slot.generation == generation && slot.object.is_some() {
+            Ok(slot)
+        } else if slot.object.is_none() && slot.generation == generation.wrapping_add(1) & GENERATION_MASK {
+            Err(FfiError::HandleFreed)
+        } else {
+            Err(FfiError::StaleHandle)
+        }
+    }
+
+    pub fn insert(&self, object: T) -> Result<FfiHandle, FfiError> {
+        let mut slots = self.slots();
+        let object = Some(Arc::new(Mutex::new(Some(object))));
+        let (index, generation) = match slots.free.pop() {
+            Some(index) => {
+                let slot = &mut slots.slots[index];
+                slot.object = object;
+                (index, slot.generation)
+            }
+            None => {
+                let index = slots.slots.len();
+                if index as u64 > INDEX_MASK {
+                    return Err(FfiError::RegistryFull);
+                }
+                slots.slots.push(Slot { generation: 0, object });
+                (index, 0)
+            }
+        };
+        slots.len += 1;
+        drop(slots);
+        Ok(self.encode(index, generation))
+    }
+
+    fn get(&self, handle: FfiHandle) -> Result<HandleObject<T>, FfiError> {
+        let (index, generation) = self.decode(handle)?;
+        let mut slots = self.slots();
+        let slot = Self::check_slot(&mut slots, index, generation)?;
+        Ok(slot.object.clone().expect("checked slots are occupied"))
+    }
+
+    pub fn remove(&self, handle: FfiHandle) -> Result<T, FfiError> {
+        let (index, generation) = self.decode(handle)?;
+        let mut slots = self.slots();
+        let slot = Self::check_slot(&mut slots, index, generation)?;
+        if slot.object.as_ref().is_some_and(is_in_use) {
+            return Err(FfiError::HandleInUse);
+        }
+        let object = slot.object.take().expect("checked slots are occupied");
+        slot.generation = slot.generation.wrapping_add(1) & GENERATION_MASK;
+        slots.free.push(index);
+        slots.len -= 1;
+        drop(slots);
+        let object = object.lock().unwrap_or_else(|e| e.into_inner()).take();
+        object.ok_or(FfiError::HandleFreed)
+    }
+
+    pub fn contains(&self, handle: FfiHandle) -> bool {
+        self.get(handle).is_ok()
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+pub fn object_to_handle<T>(context: &'static str, registry: &HandleRegistry<T>, object: T) -> FfiHandle {
+    handle_result(context, NULL_HANDLE, registry.insert(object))
+}
+
+pub fn result_to_handle<T, E: ErrorCode>(context: &'static str, registry: &HandleRegistry<T>, result: Result<T, E>) -> FfiHandle {
+    match result {
+        Ok(object) => object_to_handle(context, registry, object),
+        Err(e) => handle_result(context, NULL_HANDLE, Err(e)),
+    }
+}
+
+pub fn take_handle<T>(registry: &HandleRegistry<T>, handle: FfiHandle) -> Result<T, FfiError> {
+    registry.remove(handle)
+}
+
+pub fn with_handle<T, R, F: FnOnce(&mut T) -> R>(context: &'static str, registry: &HandleRegistry<T>, handle: FfiHandle, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    match registry.get(handle) {
+        Ok(object) => {
+            let _in_use = match InUse::enter(&object) {
+                Ok(in_use) => in_use,
+                Err(e) => {
+                    set_last_error(context, e);
+                    return error_return_value;
+                }
+            };
+            let mut object = object.lock().unwrap_or_else(|e| e.into_inner());
+            match object.as_mut() {
+                Some(object) => catch_panic(context, error_return_value, || f(object)),
+                None => {
+                    set_last_error(context, FfiError::HandleFreed);
+                    error_return_value
+                }
+            }
+        }
+        Err(e) => {
+            set_last_error(context, e);
+            error_return_value
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Barrier;
+    use std::thread;
+    use std::time::Duration;
+    use crate::error::{clear_last_error, get_last_error_code, ERROR_CODE_INVALID_HANDLE};
+
+    #[test]
+    fn round_trip() {
+        let registry = HandleRegistry::new();
+        let handle = object_to_handle("Inserting", &registry, 5);
+        assert_ne!(handle, NULL_HANDLE);
+        assert_eq!(with_handle("Using", &registry, handle, -1, |value| { *value += 1; *value }), 6);
+        assert_eq!(take_handle(&registry, handle), Ok(6));
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn freed_and_stale_handles() {
+        let registry = HandleRegistry::new();
+        let handle = registry.insert(1).unwrap();
+        registry.remove(handle).unwrap();
+        assert_eq!(registry.get(handle).err(), Some(FfiError::HandleFreed));
+        let reused = registry.insert(2).unwrap();
+        assert_ne!(reused, handle);
+        assert_eq!(registry.get(handle).err(), Some(FfiError::StaleHandle));
+        assert_eq!(take_handle(&registry, reused), Ok(2));
+    }
+
+    #[test]
+    fn wrong_registry() {
+        let first = HandleRegistry::new();
+        let second = HandleRegistry::new();
+        let handle = first.insert(1).unwrap();
+        second.insert(1).unwrap();
+        assert_eq!(second.get(handle).err(), Some(FfiError::WrongHandleType));
+        assert_eq!(second.get(NULL_HANDLE).err(), Some(FfiError::InvalidHandle));
+    }
+
+    #[test]
+    fn nested_with_handle() {
+        clear_last_error();
+        let registry = HandleRegistry::new();
+        let handle = registry.insert(1).unwrap();
+        let result = with_handle("Outer", &registry, handle, -1, |_| with_handle("Inner", &registry, handle, -2, |value| *value));
+        assert_eq!(result, -2);
+        assert_eq!(get_last_error_code(), ERROR_CODE_INVALID_HANDLE);
+        assert_eq!(registry.remove(handle), Ok(1));
+    }
+
+    #[test]
+    fn take_handle_inside_with_handle() {
+        let registry = HandleRegistry::new();
+        let handle = registry.insert(1).unwrap();
+        let result = with_handle("Using", &registry, handle, None, |_| Some(take_handle(&registry, handle)));
+        assert_eq!(result, Some(Err(FfiError::HandleInUse)));
+        assert_eq!(take_handle(&registry, handle), Ok(1));
+    }
+
+    #[test]
+    fn other_threads_wait_for_the_object() {
+        let registry = HandleRegistry::new();
+        let handle = registry.insert(0).unwrap();
+        let barrier = Barrier::new(2);
+        thread::scope(|scope| {
+            let user = scope.spawn(|| with_handle("Using", &registry, handle, -1, |value| {
+                barrier.wait();
+                thread::sleep(Duration::from_millis(50));
+                *value += 1;
+                *value
+            }));
+            barrier.wait();
+            assert_eq!(with_handle("Waiting", &registry, handle, -1, |value| *value), 1);
+            assert_eq!(user.join().unwrap(), 1);
+        });
+        assert_eq!(take_handle(&registry, handle), Ok(1));
+    }
+
+    #[test]
+    fn take_handle_waits_for_other_threads() {
+        let registry = HandleRegistry::new();
+        let handle = registry.insert(String::from("value")).unwrap();
+        let barrier = Barrier::new(2);
+        thread::scope(|scope| {
+            let user = scope.spawn(|| with_handle("Using", &registry, handle, false, |value| {
+                barrier.wait();
+                thread::sleep(Duration::from_millis(50));
+                value.push('!');
+                true
+            }));
+            barrier.wait();
+            assert_eq!(take_handle(&registry, handle).as_deref(), Ok("value!"));
+            assert!(user.join().unwrap());
+        });
+        assert!(!registry.contains(handle));
+        assert!(registry.is_empty());
+    }
+}
@@ -1,5 +1,37 @@
+mod arc;
+mod borrow;
+mod callback;
 mod error;
+mod error_stack;
+mod ffi_error;
+mod ffi_result;
+mod ffi_str;
+mod handle;
+mod out;
+mod panic;
+mod slice;
+mod status;
+mod string_buffer;
+mod tagged;
 mod util;
+mod wstr;
 
+pub use arc::*;
+pub use callback::*;
 pub use error::*;
+pub use error_stack::*;
+pub use ffi_error::*;
+pub use ffi_result::*;
+pub use ffi_str::*;
+pub use handle::*;
+pub use out::*;
+pub use panic::*;
+pub use slice::*;
+pub use status::*;
+pub use string_buffer::*;
+pub use tagged::*;
 pub use util::*;
+pub use wstr::*;
+
+#[cfg(feature = "macros")]
+pub use ffi_utils_macros::{export_error_api, ffi_export};
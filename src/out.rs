@@ -0,0 +1,117 @@
+use std::mem::MaybeUninit;
+use std::os::raw::c_char;
+use std::ptr::{self, null_mut};
+use crate::error::{set_last_error, ErrorCode};
+use crate::ffi_error::FfiError;
+use crate::panic::catch_panic;
+use crate::util::{handle_result, object_to_ptr, string_to_ptr};
+use crate::error_stack::ErrorScope;
+
+fn check_out<T>(context: &'static str, out_ptr: *mut T) -> bool {
+    if out_ptr.is_null() {
+        set_last_error(context, FfiError::NullPointer);
+        false
+    } else if !out_ptr.is_aligned() {
+        set_last_error(context, FfiError::MisalignedPointer);
+        false
+    } else {
+        true
+    }
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn write_out<T>(context: &'static str, out_ptr: *mut T, value: T) -> bool {
+    if !check_out(context, out_ptr) {
+        return false;
+    }
+    unsafe { ptr::write(out_ptr, value) };
+    true
+}
+
+pub fn with_out<T, R, F: FnOnce(&mut MaybeUninit<T>) -> R>(context: &'static str, out_ptr: *mut T, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    if !check_out(context, out_ptr) {
+        return error_return_value;
+    }
+    let out = unsafe { &mut *(out_ptr as *mut MaybeUninit<T>) };
+    catch_panic(context, error_return_value, || f(out))
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn write_out_string<S: Into<Vec<u8>>>(context: &'static str, out_ptr: *mut *mut c_char, string: S) -> bool {
+    if !check_out(context, out_ptr) {
+        return false;
+    }
+    let string_ptr = string_to_ptr(context, string);
+    unsafe { ptr::write(out_ptr, string_ptr) };
+    !string_ptr.is_null()
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn write_out_object<T>(context: &'static str, out_ptr: *mut *mut T, object: T) -> bool {
+    if !check_out(context, out_ptr) {
+        return false;
+    }
+    unsafe { ptr::write(out_ptr, object_to_ptr(object)) };
+    true
+}
+
+pub fn write_out_result<T, E: ErrorCode>(context: &'static str, out_ptr: *mut T, result: Result<T, E>) -> bool {
+    if !check_out(context, out_ptr) {
+        return false;
+    }
+    match handle_result(context, None, result.map(Some)) {
+        Some(value) => write_out(context, out_ptr, value),
+        None => false,
+    }
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn write_out_string_result<S: Into<Vec<u8>>, E: ErrorCode>(context: &'static str, out_ptr: *mut *mut c_char, result: Result<S, E>) -> bool {
+    if !check_out(context, out_ptr) {
+        return false;
+    }
+    match handle_result(context, None, result.map(Some)) {
+        Some(string) => write_out_string(context, out_ptr, string),
+        None => {
+            unsafe { ptr::write(out_ptr, null_mut()) };
+            false
+        }
+    }
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn write_out_object_result<T, E: ErrorCode>(context: &'static str, out_ptr: *mut *mut T, result: Result<T, E>) -> bool {
+    if !check_out(context, out_ptr) {
+        return false;
+    }
+    match handle_result(context, None, result.map(Some)) {
+        Some(object) => write_out_object(context, out_ptr, object),
+        None => {
+            unsafe { ptr::write(out_ptr, null_mut()) };
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use crate::util::take_ownership;
+
+    #[test]
+    fn object_round_trip() {
+        let mut out = null_mut();
+        assert!(write_out_object("Writing", &mut out, 5));
+        assert_eq!(take_ownership(out), Ok(5));
+    }
+
+    #[test]
+    fn null_out_pointer_drops_the_object() {
+        let object = Rc::new(());
+        assert!(!write_out_object("Writing", null_mut(), object.clone()));
+        assert!(!write_out_object_result("Writing", null_mut(), Ok::<_, &str>(object.clone())));
+        assert_eq!(Rc::strong_count(&object), 1);
+    }
+}
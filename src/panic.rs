@@ -0,0 +1,128 @@
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr::null_mut;
+use std::sync::RwLock;
+use crate::error::{get_last_error_sequence, set_last_error_with_code, ErrorCode, ERROR_CODE_PANIC};
+use crate::util::{handle_result, object_to_ptr};
+use crate::error_stack::{push_context_frame, ErrorScope};
+
+pub type PanicFormatter = fn(&(dyn Any + Send)) -> String;
+
+static PANIC_FORMATTER: RwLock<Option<PanicFormatter>> = RwLock::new(None);
+
+pub fn set_panic_formatter(formatter: Option<PanicFormatter>) {
+    *PANIC_FORMATTER.write().unwrap_or_else(|e| e.into_inner()) = formatter;
+}
+
+pub fn default_panic_formatter(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Unknown panic payload".to_string()
+    }
+}
+
+pub fn format_panic_payload(payload: &(dyn Any + Send)) -> String {
+    let formatter = *PANIC_FORMATTER.read().unwrap_or_else(|e| e.into_inner());
+    formatter.unwrap_or(default_panic_formatter)(payload)
+}
+
+pub fn set_last_error_from_panic(context: &'static str, payload: &(dyn Any + Send)) {
+    set_last_error_with_code(context, ERROR_CODE_PANIC, format!("Panicked: {}", format_panic_payload(payload)));
+}
+
+#[inline(always)]
+pub fn catch_panic<R, F: FnOnce() -> R>(context: &'static str, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    let sequence = get_last_error_sequence();
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(r) => {
+            push_context_frame(context, sequence);
+            r
+        }
+        Err(payload) => {
+            set_last_error_from_panic(context, payload.as_ref());
+            error_return_value
+        }
+    }
+}
+
+pub fn catch_result<T, E: ErrorCode, F: FnOnce() -> Result<T, E>>(context: &'static str, error_return_value: T, f: F) -> T {
+    let _scope = ErrorScope::enter();
+    let sequence = get_last_error_sequence();
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(t)) => {
+            push_context_frame(context, sequence);
+            t
+        }
+        Ok(Err(e)) => handle_result(context, error_return_value, Err(e)),
+        Err(payload) => {
+            set_last_error_from_panic(context, payload.as_ref());
+            error_return_value
+        }
+    }
+}
+
+pub fn catch_result_to_ptr<T, E: ErrorCode, F: FnOnce() -> Result<T, E>>(context: &'static str, f: F) -> *mut T {
+    catch_result(context, null_mut(), || f().map(object_to_ptr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use crate::error::{clear_last_error, get_last_error_code, get_last_error_context, get_last_error_message, TestSettings};
+    use crate::util::{with, with_str};
+
+    fn assert_panic_recorded(context: &str, message: &str) {
+        assert_eq!(get_last_error_code(), ERROR_CODE_PANIC);
+        assert_eq!(get_last_error_context(), context);
+        assert_eq!(get_last_error_message(), message);
+    }
+
+    #[test]
+    fn panics_in_with_are_caught() {
+        let _settings = TestSettings::lock();
+        let mut object = 1;
+        assert_eq!(with("Using object", &mut object, -1, |_| -> i32 { panic!("object failed") }), -1);
+        assert_panic_recorded("Using object", "Panicked: object failed");
+    }
+
+    #[test]
+    fn panics_in_with_str_are_caught() {
+        let _settings = TestSettings::lock();
+        let string = CString::new("value").unwrap();
+        assert!(!with_str("Using string", string.as_ptr(), false, |value| -> bool { panic!("{} failed", value) }));
+        assert_panic_recorded("Using string", "Panicked: value failed");
+    }
+
+    #[test]
+    fn panics_in_catch_result_are_caught() {
+        let _settings = TestSettings::lock();
+        assert_eq!(catch_result("Calculating", -1, || -> Result<i32, &str> { panic!("calculation failed") }), -1);
+        assert_panic_recorded("Calculating", "Panicked: calculation failed");
+        assert!(catch_result_to_ptr("Creating", || -> Result<i32, &str> { panic!("creation failed") }).is_null());
+        assert_panic_recorded("Creating", "Panicked: creation failed");
+    }
+
+    #[test]
+    fn no_panic() {
+        clear_last_error();
+        assert_eq!(catch_panic("Calculating", -1, || 5), 5);
+        assert_eq!(catch_result("Calculating", -1, || Ok::<_, &str>(5)), 5);
+        assert_eq!(get_last_error_code(), 0);
+    }
+
+    #[test]
+    fn custom_panic_formatter() {
+        let _settings = TestSettings::lock();
+        set_panic_formatter(Some(|payload| format!("custom: {}", default_panic_formatter(payload))));
+        assert_eq!(catch_panic("Calculating", -1, || -> i32 { panic!("failed") }), -1);
+        assert_panic_recorded("Calculating", "Panicked: custom: failed");
+        set_panic_formatter(None);
+        assert_eq!(catch_panic("Calculating", -1, || -> i32 { std::panic::panic_any(5) }), -1);
+        assert_panic_recorded("Calculating", "Panicked: Unknown panic payload");
+    }
+}
@@ -0,0 +1,92 @@
+use std::mem::{size_of, ManuallyDrop};
+use std::ptr::{null_mut, NonNull};
+use std::slice;
+use crate::error::{set_last_error, ErrorCode};
+use crate::panic::catch_panic;
+use crate::ffi_error::FfiError;
+use crate::util::handle_result;
+use crate::error_stack::ErrorScope;
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct FfiBuffer<T> {
+    pub ptr: *mut T,
+    pub len: usize,
+    pub cap: usize,
+}
+
+impl<T> FfiBuffer<T> {
+    pub const fn null() -> Self {
+        FfiBuffer { ptr: null_mut(), len: 0, cap: 0 }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.ptr.is_null()
+    }
+}
+
+pub(crate) fn check_slice<T>(ptr: *const T, len: usize) -> Result<*const T, FfiError> {
+    if ptr.is_null() {
+        return if len == 0 {
+            Ok(NonNull::dangling().as_ptr())
+        } else {
+            Err(FfiError::NullPointer)
+        };
+    }
+    if !ptr.is_aligned() {
+        return Err(FfiError::MisalignedPointer);
+    }
+    if len.checked_mul(size_of::<T>()).is_none_or(|size| size > isize::MAX as usize) {
+        return Err(FfiError::LengthTooLarge { len });
+    }
+    Ok(ptr)
+}
+
+#[inline(always)]
+pub fn with_slice<T, R, F: FnOnce(&[T]) -> R>(context: &'static str, ptr: *const T, len: usize, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    match check_slice(ptr, len) {
+        Ok(ptr) => {
+            let slice = unsafe { slice::from_raw_parts(ptr, len) };
+            catch_panic(context, error_return_value, || f(slice))
+        }
+        Err(e) => {
+            set_last_error(context, e);
+            error_return_value
+        }
+    }
+}
+
+#[inline(always)]
+pub fn with_slice_mut<T, R, F: FnOnce(&mut [T]) -> R>(context: &'static str, ptr: *mut T, len: usize, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    match check_slice(ptr, len) {
+        Ok(ptr) => {
+            let slice = unsafe { slice::from_raw_parts_mut(ptr as *mut T, len) };
+            catch_panic(context, error_return_value, || f(slice))
+        }
+        Err(e) => {
+            set_last_error(context, e);
+            error_return_value
+        }
+    }
+}
+
+pub fn vec_to_ptr<T>(vec: Vec<T>) -> FfiBuffer<T> {
+    let mut vec = ManuallyDrop::new(vec);
+    FfiBuffer { ptr: vec.as_mut_ptr(), len: vec.len(), cap: vec.capacity() }
+}
+
+pub fn vec_result_to_ptr<T, E: ErrorCode>(context: &'static str, result: Result<Vec<T>, E>) -> FfiBuffer<T> {
+    handle_result(context, FfiBuffer::null(), result.map(vec_to_ptr))
+}
+
+pub fn take_vec_ownership<T>(buffer: FfiBuffer<T>) -> Result<Vec<T>, FfiError> {
+    if buffer.ptr.is_null() {
+        Err(FfiError::NullPointer)
+    } else if buffer.len > buffer.cap {
+        Err(FfiError::InvalidBuffer { len: buffer.len, cap: buffer.cap })
+    } else {
+        Ok(unsafe { Vec::from_raw_parts(buffer.ptr, buffer.len, buffer.cap) })
+    }
+}
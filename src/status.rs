@@ -0,0 +1,118 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use crate::error::*;
+use crate::panic::catch_result;
+use crate::util::handle_result;
+
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FfiStatus(pub i32);
+
+impl FfiStatus {
+    pub const OK: FfiStatus = FfiStatus(ERROR_CODE_NONE);
+    pub const UNKNOWN: FfiStatus = FfiStatus(ERROR_CODE_UNKNOWN);
+    pub const NULL_POINTER: FfiStatus = FfiStatus(ERROR_CODE_NULL_POINTER);
+    pub const INVALID_UTF8: FfiStatus = FfiStatus(ERROR_CODE_INVALID_UTF8);
+    pub const INDEX_OUT_OF_BOUNDS: FfiStatus = FfiStatus(ERROR_CODE_INDEX_OUT_OF_BOUNDS);
+    pub const INTERIOR_NUL: FfiStatus = FfiStatus(ERROR_CODE_INTERIOR_NUL);
+    pub const PANIC: FfiStatus = FfiStatus(ERROR_CODE_PANIC);
+    pub const INVALID_HANDLE: FfiStatus = FfiStatus(ERROR_CODE_INVALID_HANDLE);
+    pub const TYPE_MISMATCH: FfiStatus = FfiStatus(ERROR_CODE_TYPE_MISMATCH);
+    pub const INVALID_ARGUMENT: FfiStatus = FfiStatus(ERROR_CODE_INVALID_ARGUMENT);
+    pub const BUFFER_TOO_SMALL: FfiStatus = FfiStatus(ERROR_CODE_BUFFER_TOO_SMALL);
+    pub const INVALID_UTF16: FfiStatus = FfiStatus(ERROR_CODE_INVALID_UTF16);
+    pub const CALLBACK: FfiStatus = FfiStatus(ERROR_CODE_CALLBACK);
+    pub const LOCK_POISONED: FfiStatus = FfiStatus(ERROR_CODE_LOCK_POISONED);
+    pub const ALREADY_BORROWED: FfiStatus = FfiStatus(ERROR_CODE_ALREADY_BORROWED);
+
+    pub const fn custom(code: i32) -> Self {
+        FfiStatus(code)
+    }
+
+    pub const fn code(self) -> i32 {
+        self.0
+    }
+
+    pub const fn is_ok(self) -> bool {
+        self.0 == ERROR_CODE_NONE
+    }
+
+    pub fn from_last_error() -> Self {
+        match get_last_error_code() {
+            ERROR_CODE_NONE => FfiStatus::UNKNOWN,
+            code => FfiStatus(code),
+        }
+    }
+
+    pub fn from_success(success: bool) -> Self {
+        if success {
+            FfiStatus::OK
+        } else {
+            Self::from_last_error()
+        }
+    }
+
+    fn message(self) -> Option<&'static str> {
+        Some(match self {
+            FfiStatus::OK => "Success",
+            FfiStatus::UNKNOWN => "Unknown error",
+            FfiStatus::NULL_POINTER => "Invalid pointer",
+            FfiStatus::INVALID_UTF8 => "Invalid UTF-8",
+            FfiStatus::INDEX_OUT_OF_BOUNDS => "Index out of bounds",
+            FfiStatus::INTERIOR_NUL => "String contains a NUL byte",
+            FfiStatus::PANIC => "Panicked",
+            FfiStatus::INVALID_HANDLE => "Invalid handle",
+            FfiStatus::TYPE_MISMATCH => "Wrong object type",
+            FfiStatus::INVALID_ARGUMENT => "Invalid argument",
+            FfiStatus::BUFFER_TOO_SMALL => "Buffer is too small",
+            FfiStatus::INVALID_UTF16 => "Invalid UTF-16",
+            FfiStatus::CALLBACK => "Callback failed",
+            FfiStatus::LOCK_POISONED => "Lock is poisoned",
+            FfiStatus::ALREADY_BORROWED => "Object is already borrowed",
+            _ => return None,
+        })
+    }
+}
+
+impl Default for FfiStatus {
+    fn default() -> Self {
+        FfiStatus::OK
+    }
+}
+
+impl From<FfiStatus> for i32 {
+    fn from(status: FfiStatus) -> Self {
+        status.0
+    }
+}
+
+impl From<i32> for FfiStatus {
+    fn from(code: i32) -> Self {
+        FfiStatus(code)
+    }
+}
+
+impl Display for FfiStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.message() {
+            Some(description) => write!(f, "{}", description),
+            None => write!(f, "Error code {}", self.0),
+        }
+    }
+}
+
+impl Error for FfiStatus {}
+
+impl ErrorCode for FfiStatus {
+    fn error_code(&self) -> i32 {
+        self.0
+    }
+}
+
+pub fn handle_status<E: ErrorCode>(context: &'static str, result: Result<(), E>) -> FfiStatus {
+    FfiStatus::from_success(handle_result(context, false, result.map(|()| true)))
+}
+
+pub fn catch_status<E: ErrorCode, F: FnOnce() -> Result<(), E>>(context: &'static str, f: F) -> FfiStatus {
+    FfiStatus::from_success(catch_result(context, false, || f().map(|()| true)))
+}
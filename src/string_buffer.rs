@@ -0,0 +1,80 @@
+use std::os::raw::c_char;
+use std::ptr;
+use crate::error::{get_last_error, set_last_error, ErrorCode};
+use crate::ffi_error::FfiError;
+use crate::util::handle_result;
+
+enum BufferWrite {
+    Complete,
+    Truncated,
+    NullBuffer,
+}
+
+fn copy_to_buffer(s: &str, buf: *mut c_char, buf_len: usize, out_required_len: *mut usize) -> BufferWrite {
+    let required_len = s.len() + 1;
+    if !out_required_len.is_null() {
+        unsafe { out_required_len.write(required_len) };
+    }
+    if buf.is_null() {
+        return if buf_len == 0 { BufferWrite::Truncated } else { BufferWrite::NullBuffer };
+    }
+    if buf_len == 0 {
+        return BufferWrite::Truncated;
+    }
+    let mut copy_len = s.len().min(buf_len - 1);
+    while !s.is_char_boundary(copy_len) {
+        copy_len -= 1;
+    }
+    unsafe {
+        ptr::copy_nonoverlapping(s.as_ptr() as *const c_char, buf, copy_len);
+        buf.add(copy_len).write(0);
+    }
+    if copy_len == s.len() { BufferWrite::Complete } else { BufferWrite::Truncated }
+}
+
+pub fn write_str_to_buffer(context: &'static str, s: &str, buf: *mut c_char, buf_len: usize, out_required_len: *mut usize) -> bool {
+    if let Some(position) = s.bytes().position(|b| b == 0) {
+        set_last_error(context, FfiError::InteriorNul { position });
+        return false;
+    }
+    match copy_to_buffer(s, buf, buf_len, out_required_len) {
+        BufferWrite::Complete => true,
+        BufferWrite::Truncated => {
+            set_last_error(context, FfiError::BufferTooSmall { required: s.len() + 1, available: buf_len });
+            false
+        }
+        BufferWrite::NullBuffer => {
+            set_last_error(context, FfiError::NullPointer);
+            false
+        }
+    }
+}
+
+pub fn write_string_result_to_buffer<S: AsRef<str>, E: ErrorCode>(context: &'static str, result: Result<S, E>, buf: *mut c_char, buf_len: usize, out_required_len: *mut usize) -> bool {
+    match result {
+        Ok(s) => write_str_to_buffer(context, s.as_ref(), buf, buf_len, out_required_len),
+        Err(e) => handle_result(context, false, Err(e)),
+    }
+}
+
+pub fn write_last_error_to_buffer(buf: *mut c_char, buf_len: usize, out_required_len: *mut usize) -> bool {
+    let last_error = get_last_error().replace('\0', "");
+    matches!(copy_to_buffer(&last_error, buf, buf_len, out_required_len), BufferWrite::Complete)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{get_last_error_code, set_auto_clear_last_error, TestSettings, ERROR_CODE_BUFFER_TOO_SMALL};
+
+    #[test]
+    fn result_keeps_buffer_errors_with_auto_clear() {
+        let _settings = TestSettings::lock();
+        set_auto_clear_last_error(true);
+        let mut buf = [0 as c_char; 2];
+        let mut required_len = 0;
+        assert!(!write_string_result_to_buffer("Writing", Ok::<_, &str>("hello"), buf.as_mut_ptr(), buf.len(), &mut required_len));
+        assert_eq!(required_len, 6);
+        assert_eq!(get_last_error_code(), ERROR_CODE_BUFFER_TOO_SMALL);
+    }
+}
@@ -0,0 +1,137 @@
+use std::alloc::{dealloc, Layout};
+use std::any::{type_name, TypeId};
+use std::collections::VecDeque;
+use std::ptr::{self, null_mut};
+use std::sync::Mutex;
+use crate::borrow::BorrowGuard;
+use crate::error::{set_last_error, ErrorCode};
+use crate::ffi_error::FfiError;
+use crate::panic::catch_panic;
+use crate::util::handle_result;
+use crate::error_stack::ErrorScope;
+
+const TAG_MAGIC: u64 = 0x4646_4954_4147_4745;
+const TAG_FREED: u64 = 0x4646_4946_5245_4544;
+const QUARANTINE_SIZE: usize = 256;
+
+#[repr(C)]
+struct TagHeader {
+    magic: u64,
+    type_id: TypeId,
+    type_name: &'static str,
+}
+
+#[repr(C)]
+pub struct Tagged<T> {
+    header: TagHeader,
+    object: T,
+}
+
+static QUARANTINE: Mutex<VecDeque<(usize, Layout)>> = Mutex::new(VecDeque::new());
+
+fn check_tag<T: 'static>(t_ptr: *mut Tagged<T>) -> Result<*mut Tagged<T>, FfiError> {
+    let expected = type_name::<T>();
+    if t_ptr.is_null() {
+        return Err(FfiError::NullPointer);
+    }
+    let header = t_ptr as *const TagHeader;
+    if !header.is_aligned() {
+        return Err(FfiError::NotTagged { expected });
+    }
+    match unsafe { ptr::read_volatile(ptr::addr_of!((*header).magic)) } {
+        TAG_MAGIC => {
+            let header = unsafe { &*header };
+            if header.type_id == TypeId::of::<T>() {
+                Ok(t_ptr)
+            } else {
+                Err(FfiError::TypeMismatch { expected, found: header.type_name })
+            }
+        }
+        TAG_FREED => Err(FfiError::AlreadyFreed { expected }),
+        _ => Err(FfiError::NotTagged { expected }),
+    }
+}
+
+pub fn object_to_tagged_ptr<T: 'static>(object: T) -> *mut Tagged<T> {
+    let header = TagHeader { magic: TAG_MAGIC, type_id: TypeId::of::<T>(), type_name: type_name::<T>() };
+    Box::into_raw(Box::new(Tagged { header, object }))
+}
+
+pub fn tagged_result_to_ptr<T: 'static, E: ErrorCode>(context: &'static str, result: Result<T, E>) -> *mut Tagged<T> {
+    handle_result(context, null_mut(), result.map(object_to_tagged_ptr))
+}
+
+pub fn take_tagged_ownership<T: 'static>(t_ptr: *mut Tagged<T>) -> Result<T, FfiError> {
+    let tagged = check_tag(t_ptr)?;
+    let object = unsafe {
+        let object = ptr::read(ptr::addr_of!((*tagged).object));
+        ptr::write_volatile(ptr::addr_of_mut!((*tagged).header.magic), TAG_FREED);
+        object
+    };
+    let mut quarantine = QUARANTINE.lock().unwrap_or_else(|e| e.into_inner());
+    quarantine.push_back((tagged as usize, Layout::new::<Tagged<T>>()));
+    if quarantine.len() > QUARANTINE_SIZE {
+        if let Some((freed, layout)) = quarantine.pop_front() {
+            unsafe { dealloc(freed as *mut u8, layout) };
+        }
+    }
+    Ok(object)
+}
+
+#[inline(always)]
+pub fn with_tagged<T: 'static, R, F: FnOnce(&mut T) -> R>(context: &'static str, t_ptr: *mut Tagged<T>, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    match check_tag(t_ptr) {
+        Ok(tagged) => match BorrowGuard::exclusive(t_ptr) {
+            Ok(_borrow) => catch_panic(context, error_return_value, || f(unsafe { &mut (*tagged).object })),
+            Err(e) => {
+                set_last_error(context, e);
+                error_return_value
+            }
+        },
+        Err(e) => {
+            set_last_error(context, e);
+            error_return_value
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{clear_last_error, get_last_error_code, ERROR_CODE_TYPE_MISMATCH};
+
+    #[test]
+    fn round_trip() {
+        let ptr = object_to_tagged_ptr(5u32);
+        assert_eq!(with_tagged("Using", ptr, 0, |value| { *value += 1; *value }), 6);
+        assert_eq!(take_tagged_ownership(ptr), Ok(6));
+    }
+
+    #[test]
+    fn wrong_type() {
+        clear_last_error();
+        let ptr = object_to_tagged_ptr(5u32);
+        let wrong = ptr as *mut Tagged<String>;
+        assert_eq!(take_tagged_ownership(wrong), Err(FfiError::TypeMismatch { expected: type_name::<String>(), found: type_name::<u32>() }));
+        assert_eq!(with_tagged("Using", wrong, -1, |_| 0), -1);
+        assert_eq!(get_last_error_code(), ERROR_CODE_TYPE_MISMATCH);
+        assert_eq!(take_tagged_ownership(ptr), Ok(5));
+    }
+
+    #[test]
+    fn double_free() {
+        let ptr = object_to_tagged_ptr(String::from("value"));
+        assert_eq!(take_tagged_ownership(ptr).as_deref(), Ok("value"));
+        assert_eq!(take_tagged_ownership(ptr), Err(FfiError::AlreadyFreed { expected: type_name::<String>() }));
+        assert!(!with_tagged("Using", ptr, false, |_| true));
+    }
+
+    #[test]
+    fn null_and_untagged() {
+        assert_eq!(take_tagged_ownership::<u64>(null_mut()), Err(FfiError::NullPointer));
+        let mut untagged = [0u64; 8];
+        let ptr = untagged.as_mut_ptr() as *mut Tagged<u64>;
+        assert_eq!(take_tagged_ownership(ptr), Err(FfiError::NotTagged { expected: type_name::<u64>() }));
+    }
+}
@@ -1,9 +1,16 @@
+use std::borrow::Cow;
 use std::error::Error;
 use std::ffi::{CStr, CString};
-use std::fmt::Display;
 use std::os::raw::c_char;
 use std::ptr::null_mut;
-use crate::error::set_last_error;
+use std::slice;
+use std::str::{self, Utf8Error};
+use crate::borrow::BorrowGuard;
+use crate::panic::catch_panic;
+use crate::slice::{check_slice, vec_to_ptr, FfiBuffer};
+use crate::ffi_error::FfiError;
+use crate::error::{set_last_error, ErrorCode};
+use crate::error_stack::ErrorScope;
 
 pub const fn bool_to_u8(b: bool) -> u8 {
     if b { 1 } else { 0 }
@@ -18,26 +25,34 @@ pub fn object_to_ptr<T>(object: T) -> *mut T {
 }
 
 pub fn string_to_ptr<S: Into<Vec<u8>>>(context: &'static str, string: S) -> *mut c_char {
-    handle_result(context, null_mut(), CString::new(string).map(CString::into_raw))
+    match CString::new(string) {
+        Ok(string) => string.into_raw(),
+        Err(e) => {
+            set_last_error(context, FfiError::from(e));
+            null_mut()
+        }
+    }
 }
 
-pub fn take_ownership<T>(raw_ptr: *mut T) -> Result<T, &'static str> {
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn take_ownership<T>(raw_ptr: *mut T) -> Result<T, FfiError> {
     if raw_ptr.is_null() {
-        Err("Invalid pointer")
+        Err(FfiError::NullPointer)
     } else {
         Ok(*(unsafe { Box::from_raw(raw_ptr) }))
     }
 }
 
-pub fn take_string_ownership(string_ptr: *mut c_char) -> Result<CString, &'static str> {
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn take_string_ownership(string_ptr: *mut c_char) -> Result<CString, FfiError> {
     if string_ptr.is_null() {
-        Err("Invalid string pointer")
+        Err(FfiError::NullPointer)
     } else {
         Ok(unsafe { CString::from_raw(string_ptr) })
     }
 }
 
-pub fn handle_result<T, E: Display>(context: &'static str, error_return_value: T, result: Result<T, E>) -> T {
+pub fn handle_result<T, E: ErrorCode>(context: &'static str, error_return_value: T, result: Result<T, E>) -> T {
     match result {
         Ok(t) => t,
         Err(e) => {
@@ -47,7 +62,7 @@ pub fn handle_result<T, E: Display>(context: &'static str, error_return_value: T
     }
 }
 
-pub fn result_to_ptr<T, E: Display>(context: &'static str, result: Result<T, E>) -> *mut T {
+pub fn result_to_ptr<T, E: ErrorCode>(context: &'static str, result: Result<T, E>) -> *mut T {
     handle_result(context, null_mut(), result.map(object_to_ptr))
 }
 
@@ -64,10 +79,56 @@ macro_rules! handle_result {
     }
 }
 
-pub fn string_result_to_ptr<S: Into<Vec<u8>>, E: Display>(context: &'static str, result: Result<S, E>) -> *mut c_char {
+pub fn string_result_to_ptr<S: Into<Vec<u8>>, E: ErrorCode>(context: &'static str, result: Result<S, E>) -> *mut c_char {
     string_to_ptr(context, handle_result!(context, null_mut(), result))
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NulPolicy {
+    #[default]
+    Error,
+    Truncate,
+    Strip,
+    Replace(u8),
+}
+
+impl NulPolicy {
+    pub fn apply(self, mut bytes: Vec<u8>) -> Vec<u8> {
+        match self {
+            NulPolicy::Error => {}
+            NulPolicy::Truncate => {
+                if let Some(position) = bytes.iter().position(|&b| b == 0) {
+                    bytes.truncate(position);
+                }
+            }
+            NulPolicy::Strip => bytes.retain(|&b| b != 0),
+            NulPolicy::Replace(replacement) => bytes.iter_mut().filter(|b| **b == 0).for_each(|b| *b = replacement),
+        }
+        bytes
+    }
+}
+
+pub fn string_to_ptr_with_nul_policy<S: Into<Vec<u8>>>(context: &'static str, string: S, policy: NulPolicy) -> *mut c_char {
+    string_to_ptr(context, policy.apply(string.into()))
+}
+
+pub fn string_result_to_ptr_with_nul_policy<S: Into<Vec<u8>>, E: ErrorCode>(context: &'static str, result: Result<S, E>, policy: NulPolicy) -> *mut c_char {
+    string_to_ptr_with_nul_policy(context, handle_result!(context, null_mut(), result), policy)
+}
+
+pub fn string_to_buffer<S: Into<Vec<u8>>>(string: S) -> FfiBuffer<u8> {
+    let mut bytes = string.into();
+    let len = bytes.len();
+    bytes.push(0);
+    let mut buffer = vec_to_ptr(bytes);
+    buffer.len = len;
+    buffer
+}
+
+pub fn string_result_to_buffer<S: Into<Vec<u8>>, E: ErrorCode>(context: &'static str, result: Result<S, E>) -> FfiBuffer<u8> {
+    handle_result(context, FfiBuffer::null(), result.map(string_to_buffer))
+}
+
 pub fn flatten_result<T, E>(result: Result<Result<T, E>, E>) -> Result<T, E> {
     match result {
         Ok(r) => r,
@@ -84,34 +145,184 @@ pub fn flatten_mismatched_result<T, E1: Into<Box<dyn Error>>, E2: Into<Box<dyn E
 }
 
 #[inline(always)]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub fn with<T, R, F: FnOnce(&mut T) -> R>(context: &'static str, t_ptr: *mut T, error_return_value: R, f: F) -> R {
-    if let Some(t) = unsafe { t_ptr.as_mut() } {
-        f(t)
-    } else {
-        set_last_error(context, "Invalid pointer");
-        error_return_value
+    let _scope = ErrorScope::enter();
+    if t_ptr.is_null() {
+        set_last_error(context, FfiError::NullPointer);
+        return error_return_value;
+    }
+    match BorrowGuard::exclusive(t_ptr) {
+        Ok(_borrow) => catch_panic(context, error_return_value, || f(unsafe { &mut *t_ptr })),
+        Err(e) => {
+            set_last_error(context, e);
+            error_return_value
+        }
+    }
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn with_ref<T, R, F: FnOnce(&T) -> R>(context: &'static str, t_ptr: *const T, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    if t_ptr.is_null() {
+        set_last_error(context, FfiError::NullPointer);
+        return error_return_value;
+    }
+    match BorrowGuard::shared(t_ptr) {
+        Ok(_borrow) => catch_panic(context, error_return_value, || f(unsafe { &*t_ptr })),
+        Err(e) => {
+            set_last_error(context, e);
+            error_return_value
+        }
     }
 }
 
 #[inline(always)]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub fn with_str<R, F: FnOnce(&str) -> R>(context: &'static str, c_str: *const c_char, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
     if c_str.is_null() {
-        return f("");
+        return catch_panic(context, error_return_value, || f(""));
     }
     let str = unsafe { CStr::from_ptr(c_str) };
     match str.to_str() {
-        Ok(s) => f(s),
-        Err(_) => {
-            set_last_error(context, "Invalid string pointer");
+        Ok(s) => catch_panic(context, error_return_value, || f(s)),
+        Err(e) => {
+            set_utf8_error(context, e);
             error_return_value
         }
     }
 }
 
-pub fn safe_index<T>(vector: &Vec<T>, index: usize) -> Result<&T, &'static str> {
+#[inline(always)]
+pub fn with_opt_str<R, F: FnOnce(Option<&str>) -> R>(context: &'static str, c_str: *const c_char, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    if c_str.is_null() {
+        return catch_panic(context, error_return_value, || f(None));
+    }
+    with_str(context, c_str, error_return_value, |s| f(Some(s)))
+}
+
+#[inline(always)]
+pub fn with_str_strict<R, F: FnOnce(&str) -> R>(context: &'static str, c_str: *const c_char, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    if c_str.is_null() {
+        set_last_error(context, FfiError::NullPointer);
+        return error_return_value;
+    }
+    with_str(context, c_str, error_return_value, f)
+}
+
+#[inline(always)]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn with_str_lossy<R, F: FnOnce(Cow<str>) -> R>(context: &'static str, c_str: *const c_char, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    if c_str.is_null() {
+        return catch_panic(context, error_return_value, || f(Cow::Borrowed("")));
+    }
+    let str = unsafe { CStr::from_ptr(c_str) };
+    catch_panic(context, error_return_value, || f(str.to_string_lossy()))
+}
+
+#[inline(always)]
+pub fn with_str_len<R, F: FnOnce(&str) -> R>(context: &'static str, c_str: *const c_char, len: usize, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    let Some(bytes) = bytes_from_raw(context, c_str, len) else {
+        return error_return_value;
+    };
+    match str::from_utf8(bytes) {
+        Ok(s) => catch_panic(context, error_return_value, || f(s)),
+        Err(e) => {
+            set_utf8_error(context, e);
+            error_return_value
+        }
+    }
+}
+
+#[inline(always)]
+pub fn with_str_len_lossy<R, F: FnOnce(Cow<str>) -> R>(context: &'static str, c_str: *const c_char, len: usize, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    match bytes_from_raw(context, c_str, len) {
+        Some(bytes) => catch_panic(context, error_return_value, || f(String::from_utf8_lossy(bytes))),
+        None => error_return_value,
+    }
+}
+
+#[inline(always)]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn with_bytes<R, F: FnOnce(&[u8]) -> R>(context: &'static str, c_str: *const c_char, len: usize, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    match bytes_from_raw(context, c_str, len) {
+        Some(bytes) => catch_panic(context, error_return_value, || f(bytes)),
+        None => error_return_value,
+    }
+}
+
+#[inline(always)]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn with_cstr<R, F: FnOnce(&CStr) -> R>(context: &'static str, c_str: *const c_char, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    let str = if c_str.is_null() { c"" } else { unsafe { CStr::from_ptr(c_str) } };
+    catch_panic(context, error_return_value, || f(str))
+}
+
+fn bytes_from_raw<'a>(context: &'static str, c_str: *const c_char, len: usize) -> Option<&'a [u8]> {
+    match check_slice(c_str as *const u8, len) {
+        Ok(ptr) => Some(unsafe { slice::from_raw_parts(ptr, len) }),
+        Err(e) => {
+            set_last_error(context, e);
+            None
+        }
+    }
+}
+
+fn set_utf8_error(context: &'static str, error: Utf8Error) {
+    set_last_error(context, FfiError::from(error));
+}
+
+pub fn safe_index<T>(vector: &[T], index: usize) -> Result<&T, FfiError> {
     if index < vector.len() {
         Ok(&vector[index])
     } else {
-        Err("Index out of bounds")
+        Err(FfiError::IndexOutOfBounds { index, len: vector.len() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slice::take_vec_ownership;
+
+    #[test]
+    fn string_buffer_keeps_interior_nuls() {
+        let buffer = string_to_buffer(&b"a\0b\0"[..]);
+        assert_eq!(buffer.len, 4);
+        assert_eq!(unsafe { CStr::from_ptr(buffer.ptr as *const c_char) }.to_bytes(), b"a");
+        assert_eq!(unsafe { *buffer.ptr.add(buffer.len) }, 0);
+        assert_eq!(take_vec_ownership(buffer), Ok(b"a\0b\0".to_vec()));
+    }
+
+    #[test]
+    fn empty_string_buffer() {
+        let buffer = string_to_buffer("");
+        assert!(!buffer.is_null());
+        assert_eq!(buffer.len, 0);
+        assert_eq!(take_vec_ownership(buffer), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn string_buffer_result() {
+        assert!(string_result_to_buffer("Getting data", Err::<String, _>("failed")).is_null());
+        let buffer = string_result_to_buffer("Getting data", Ok::<_, &str>("data"));
+        assert_eq!(take_vec_ownership(buffer), Ok(b"data".to_vec()));
+    }
+
+    #[test]
+    fn nul_policies() {
+        let data = b"a\0b".to_vec();
+        assert_eq!(NulPolicy::Truncate.apply(data.clone()), b"a");
+        assert_eq!(NulPolicy::Strip.apply(data.clone()), b"ab");
+        assert_eq!(NulPolicy::Replace(b'?').apply(data.clone()), b"a?b");
+        assert!(string_to_ptr_with_nul_policy("Converting", data, NulPolicy::Error).is_null());
     }
 }
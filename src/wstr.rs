@@ -0,0 +1,90 @@
+use std::char::REPLACEMENT_CHARACTER;
+use std::ptr::{self, null_mut};
+use std::slice;
+use crate::error::{set_last_error, ErrorCode};
+use crate::panic::catch_panic;
+use crate::ffi_error::FfiError;
+use crate::handle_result;
+use crate::error_stack::ErrorScope;
+
+unsafe fn wstr_len(w_str: *const u16) -> usize {
+    let mut len = 0;
+    while *w_str.add(len) != 0 {
+        len += 1;
+    }
+    len
+}
+
+fn decode_utf16(units: &[u16], lossy: bool) -> Result<String, usize> {
+    let mut string = String::with_capacity(units.len());
+    let mut index = 0;
+    for c in char::decode_utf16(units.iter().copied()) {
+        match c {
+            Ok(c) => {
+                index += c.len_utf16();
+                string.push(c);
+            }
+            Err(_) if lossy => {
+                index += 1;
+                string.push(REPLACEMENT_CHARACTER);
+            }
+            Err(_) => return Err(index),
+        }
+    }
+    Ok(string)
+}
+
+fn with_wstr_impl<R, F: FnOnce(&str) -> R>(context: &'static str, w_str: *const u16, lossy: bool, error_return_value: R, f: F) -> R {
+    let _scope = ErrorScope::enter();
+    if w_str.is_null() {
+        return catch_panic(context, error_return_value, || f(""));
+    }
+    if !w_str.is_aligned() {
+        set_last_error(context, FfiError::MisalignedPointer);
+        return error_return_value;
+    }
+    let units = unsafe { slice::from_raw_parts(w_str, wstr_len(w_str)) };
+    match decode_utf16(units, lossy) {
+        Ok(s) => catch_panic(context, error_return_value, || f(&s)),
+        Err(index) => {
+            set_last_error(context, FfiError::InvalidUtf16 { index });
+            error_return_value
+        }
+    }
+}
+
+#[inline(always)]
+pub fn with_wstr<R, F: FnOnce(&str) -> R>(context: &'static str, w_str: *const u16, error_return_value: R, f: F) -> R {
+    with_wstr_impl(context, w_str, false, error_return_value, f)
+}
+
+#[inline(always)]
+pub fn with_wstr_lossy<R, F: FnOnce(&str) -> R>(context: &'static str, w_str: *const u16, error_return_value: R, f: F) -> R {
+    with_wstr_impl(context, w_str, true, error_return_value, f)
+}
+
+pub fn wstring_to_ptr<S: AsRef<str>>(context: &'static str, string: S) -> *mut u16 {
+    let mut units: Vec<u16> = string.as_ref().encode_utf16().collect();
+    if let Some(index) = units.iter().position(|&unit| unit == 0) {
+        set_last_error(context, FfiError::InteriorNul { position: index });
+        return null_mut();
+    }
+    units.push(0);
+    Box::into_raw(units.into_boxed_slice()) as *mut u16
+}
+
+pub fn wstring_result_to_ptr<S: AsRef<str>, E: ErrorCode>(context: &'static str, result: Result<S, E>) -> *mut u16 {
+    wstring_to_ptr(context, handle_result!(context, null_mut(), result))
+}
+
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn take_wstring_ownership(w_str: *mut u16) -> Result<Vec<u16>, FfiError> {
+    if w_str.is_null() {
+        Err(FfiError::NullPointer)
+    } else {
+        let len = unsafe { wstr_len(w_str) };
+        let mut units = unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(w_str, len + 1)) }.into_vec();
+        units.truncate(len);
+        Ok(units)
+    }
+}
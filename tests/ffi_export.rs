@@ -0,0 +1,189 @@
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr::{null, null_mut};
+use ffi_utils::*;
+
+struct Counter {
+    value: i32,
+}
+
+#[ffi_export(error = -1)]
+fn counter_add(counter: &mut Counter, amount: i32) -> i32 {
+    counter.value += amount;
+    counter.value
+}
+
+#[ffi_export(error = -1)]
+fn counter_get(counter: &Counter) -> i32 {
+    counter.value
+}
+
+#[ffi_export]
+fn counter_new(value: i32) -> Box<Counter> {
+    Box::new(Counter { value })
+}
+
+#[ffi_export]
+fn counter_parse(text: &str) -> Result<Box<Counter>, std::num::ParseIntError> {
+    Ok(Box::new(Counter { value: text.parse()? }))
+}
+
+#[ffi_export]
+fn counter_reset(counter: &mut Counter) {
+    counter.value = 0;
+}
+
+#[ffi_export]
+fn counter_check(counter: &Counter, limit: i32) -> Result<(), String> {
+    match counter.value <= limit {
+        true => Ok(()),
+        false => Err(format!("{} is over {}", counter.value, limit)),
+    }
+}
+
+#[ffi_export]
+fn counter_is_zero(counter: &Counter) -> bool {
+    counter.value == 0
+}
+
+#[ffi_export(error = -1)]
+fn text_len(text: &str) -> i32 {
+    text.len() as i32
+}
+
+#[ffi_export(error = -1)]
+fn strict_text_len(#[not_null] text: &str) -> i32 {
+    text.len() as i32
+}
+
+#[ffi_export(error = -1)]
+fn opt_text_len(text: Option<&str>) -> i32 {
+    text.map_or(-2, |text| text.len() as i32)
+}
+
+#[ffi_export]
+fn negate(value: bool) -> bool {
+    !value
+}
+
+#[ffi_export(error = -1)]
+fn checked_double(value: i32) -> Result<i32, &'static str> {
+    value.checked_mul(2).ok_or("overflow")
+}
+
+#[ffi_export]
+fn greeting(name: &str) -> String {
+    format!("Hello, {}!", name)
+}
+
+#[ffi_export]
+fn parse_greeting(count: &str) -> Result<String, std::num::ParseIntError> {
+    Ok("Hello! ".repeat(count.parse()?))
+}
+
+#[ffi_export(context = "Exploding", error = -1, name = "ExplodeCounter")]
+fn explode(counter: &mut Counter) -> i32 {
+    panic!("boom at {}", counter.value)
+}
+
+fn c_str(text: &CStr) -> *const c_char {
+    text.as_ptr()
+}
+
+fn take_string(string_ptr: *mut c_char) -> String {
+    take_string_ownership(string_ptr).unwrap().into_string().unwrap()
+}
+
+#[test]
+fn boxed_and_reference_parameters() {
+    let counter = counter_new(1);
+    assert_eq!(counter_add(counter, 2), 3);
+    assert_eq!(counter_get(counter), 3);
+    assert_eq!(counter_is_zero(counter), 0);
+    counter_reset(counter);
+    assert_eq!(counter_is_zero(counter), 1);
+    assert_eq!(take_ownership(counter).unwrap().value, 0);
+}
+
+#[test]
+fn null_pointers() {
+    clear_last_error();
+    assert_eq!(counter_add(null_mut(), 1), -1);
+    assert_eq!(get_last_error_code(), ERROR_CODE_NULL_POINTER);
+    assert_eq!(get_last_error_context(), "counter_add");
+    clear_last_error();
+    assert_eq!(counter_get(null()), -1);
+    assert_eq!(get_last_error_code(), ERROR_CODE_NULL_POINTER);
+    clear_last_error();
+    assert_eq!(counter_is_zero(null()), 0);
+    assert_eq!(get_last_error_code(), ERROR_CODE_NULL_POINTER);
+    clear_last_error();
+    assert_eq!(counter_check(null(), 0), 0);
+    assert_eq!(get_last_error_code(), ERROR_CODE_NULL_POINTER);
+    clear_last_error();
+    counter_reset(null_mut());
+    assert_eq!(get_last_error_code(), ERROR_CODE_NULL_POINTER);
+}
+
+#[test]
+fn string_parameters() {
+    assert_eq!(text_len(c_str(c"four")), 4);
+    assert_eq!(text_len(null()), 0);
+    assert_eq!(strict_text_len(c_str(c"four")), 4);
+    clear_last_error();
+    assert_eq!(strict_text_len(null()), -1);
+    assert_eq!(get_last_error_code(), ERROR_CODE_NULL_POINTER);
+    assert_eq!(opt_text_len(c_str(c"four")), 4);
+    assert_eq!(opt_text_len(null()), -2);
+    clear_last_error();
+    assert_eq!(text_len(c_str(c"\xff")), -1);
+    assert_eq!(get_last_error_code(), ERROR_CODE_INVALID_UTF8);
+}
+
+#[test]
+fn bool_parameters_and_returns() {
+    assert_eq!(negate(0), 1);
+    assert_eq!(negate(1), 0);
+    assert_eq!(negate(2), 0);
+}
+
+#[test]
+fn fallible_returns() {
+    assert_eq!(checked_double(4), 8);
+    clear_last_error();
+    assert_eq!(checked_double(i32::MAX), -1);
+    assert_eq!(get_last_error_message(), "overflow");
+
+    let counter = counter_new(5);
+    assert_eq!(counter_check(counter, 10), 1);
+    clear_last_error();
+    assert_eq!(counter_check(counter, 1), 0);
+    assert_eq!(get_last_error_message(), "5 is over 1");
+    assert_eq!(take_ownership(counter).unwrap().value, 5);
+
+    let counter = counter_parse(c_str(c"12"));
+    assert_eq!(take_ownership(counter).unwrap().value, 12);
+    clear_last_error();
+    assert!(counter_parse(c_str(c"twelve")).is_null());
+    assert_eq!(get_last_error_context(), "counter_parse");
+}
+
+#[test]
+fn string_returns() {
+    assert_eq!(take_string(greeting(c_str(c"C"))), "Hello, C!");
+    assert_eq!(take_string(parse_greeting(c_str(c"2"))), "Hello! Hello! ");
+    clear_last_error();
+    assert!(parse_greeting(c_str(c"two")).is_null());
+    assert_eq!(get_last_error_context(), "parse_greeting");
+}
+
+#[test]
+fn panics_are_caught() {
+    let counter = counter_new(7);
+    clear_last_error();
+    assert_eq!(ExplodeCounter(counter), -1);
+    assert_eq!(get_last_error_code(), ERROR_CODE_PANIC);
+    assert_eq!(get_last_error_context(), "Exploding");
+    assert!(get_last_error_message().contains("boom at 7"));
+    assert_eq!(take_ownership(counter).unwrap().value, 7);
+}